@@ -2,51 +2,69 @@ extern crate todo_swamp;
 
 use todo_swamp::*;
 
-use std::io::{self, prelude::*};
+use std::io::{self, prelude::*, IsTerminal};
+use std::env;
 use std::fs;
 use std::time;
 
 pub fn main() -> io::Result<()> {
-    standard_run(TodoList::new())
+    let format = if env::args().any(|arg| arg == "--json-lines") { OutputFormat::JsonLines } else { OutputFormat::Text };
+    let interactive = env::args().any(|arg| arg == "--interactive") || io::stdin().is_terminal();
+    standard_run(TodoList::new(), format, interactive)
 }
 
 //runs the program, taking input from the standard input and outputs to the standard output
+//output is buffered unless interactive, in which case each result is shown as soon as it's ready
 #[allow(dead_code)]
-fn standard_run<T: TodoLister>(mut tl: T) -> io::Result<()> {
+fn standard_run<T: TodoLister>(mut tl: T, format: OutputFormat, interactive: bool) -> io::Result<()> {
     let stdin = io::stdin();
     let stdout = io::stdout();
-    let mut lines_in = stdin.lock().lines();
-    let mut buffer_out = stdout.lock();
-    if let Some(Ok(_s)) = lines_in.next() { //read first line as query count, loop on remaining lines
-        for line in lines_in {
-            if let Ok(l) = line {
-                if let Some(r) = runner::run_line(&l, &mut tl) {
-                    writeln!(buffer_out, "{}", r)?;
-                }
-            }
-        }
-    }
-    Ok(())
+    let mut buffer_out = io::BufWriter::new(stdout.lock());
+    runner::run_reader(stdin.lock(), &mut buffer_out, &mut tl, &runner::RunnerConfig::default(), format, interactive, None)?; //first line is the query count
+    buffer_out.flush()
 }
 
 //takes input from the specified file 
 //outputs to an output file
+//with the gzip feature the input can be gzipped, and is read from a .in.gz file when there's no .in file, the output is plain
 #[allow(dead_code)]
 fn file_run<T: TodoLister>(file_name: &str, append: &str, mut tl: T) -> io::Result<()> {
-    let file_in = fs::File::open(format!("{}.in", file_name))?;
+    let file_in = open_input(file_name)?;
     let file_out = fs::File::create(format!("{}_{}.out", file_name, append))?;
-    let mut lines_in = io::BufReader::new(file_in).lines();
     let mut buffer_out = io::BufWriter::new(file_out);
-    if let Some(Ok(_s)) = lines_in.next() {
-        for line in lines_in {
-            if let Ok(l) = line {
-                if let Some(r) = runner::run_line(&l, &mut tl) {
-                    writeln!(buffer_out, "{}", r)?;
-                }
-            }
+    let input = runner::input_reader(io::BufReader::new(file_in))?;
+    runner::run_reader(input, &mut buffer_out, &mut tl, &runner::RunnerConfig::default(), OutputFormat::Text, false, None)?;
+    buffer_out.flush()
+}
+
+#[allow(dead_code)]
+fn open_input(file_name: &str) -> io::Result<fs::File> {
+    let opened = fs::File::open(format!("{}.in", file_name));
+    #[cfg(feature = "gzip")]
+    let opened = opened.or_else(|_| fs::File::open(format!("{}.in.gz", file_name)));
+    opened
+}
+
+//takes input from each of the specified files in turn, all run against the same list
+//outputs to a single output file, named after the first input file
+//only the first file's count line (if it has one) is skipped, the other files are all queries
+#[allow(dead_code)]
+fn file_run_multi<T: TodoLister>(files: &[&str], append: &str, mut tl: T) -> io::Result<()> {
+    let Some(first) = files.first() else {
+        return Ok(())
+    };
+    let file_out = fs::File::create(format!("{}_{}.out", first, append))?;
+    let mut buffer_out = io::BufWriter::new(file_out);
+    let config = runner::RunnerConfig::default();
+    for (n, file_name) in files.iter().enumerate() {
+        let file_in = fs::File::open(format!("{}.in", file_name))?;
+        let mut lines_in = runner::readable_lines(io::BufReader::new(file_in).lines()).peekable();
+        if n == 0 {
+            lines_in.next_if(|l| l.trim().parse::<usize>().is_ok());
         }
+        runner::run_lines(lines_in, &mut buffer_out, &mut tl, &config, OutputFormat::Text, false, None)?;
     }
-    Ok(())
+    buffer_out.flush()
 }
 
 //takes input from the specified file
@@ -132,6 +150,8 @@ fn correctness_all(test: &str, num_commands: usize) -> io::Result<()> {
     correctness_run("tests", test, "trie2", TriedoList::<Trie2>::new(), num_commands)?;
     correctness_run("tests", test, "trie3", TriedoList::<Trie3>::new(), num_commands)?;
     correctness_run("tests", test, "trie4", TriedoList::<Trie4>::new(), num_commands)?;
+    correctness_run("tests", test, "char", TriedoList::<CharTrie>::new(), num_commands)?;
+    correctness_run("tests", test, "radix", TriedoList::<RadixTrie>::new(), num_commands)?;
     Ok(())
 }
 
@@ -175,4 +195,23 @@ pub fn black_box<T>(dummy: T) -> T {
         std::mem::forget(dummy);
         ret
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiple_files_in_order() {
+        let base = env::temp_dir().join(format!("todo_swamp_multi_{}", std::process::id()));
+        let (first, second) = (format!("{}_a", base.display()), format!("{}_b", base.display()));
+        fs::write(format!("{}.in", first), "2\nadd \"buy milk\"\nadd \"water plants\"\n").unwrap();
+        fs::write(format!("{}.in", second), "search wa\nadd \"walk dog\"\n").unwrap();
+        file_run_multi(&[&first, &second], "multi", TodoList::new()).unwrap();
+        let out = fs::read_to_string(format!("{}_multi.out", first)).unwrap();
+        for file in [format!("{}.in", first), format!("{}.in", second), format!("{}_multi.out", first)] {
+            fs::remove_file(file).unwrap();
+        }
+        assert_eq!(out.lines().collect::<Vec<&str>>(), ["0", "1", "1 item(s) found", "1 \"water plants\" ", "2"]);
+    }
+}
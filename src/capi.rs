@@ -0,0 +1,96 @@
+//C interface for embedding a todo list in a non-Rust host (enabled with the "capi" feature)
+//ownership rules:
+//-todo_list_new returns a list owned by the caller, which must be released with todo_list_free
+//-todo_run_line borrows the list and the line, and returns a string owned by the caller (or null),
+// which must be released with todo_string_free rather than the host's own free
+#![allow(clippy::missing_safety_doc)] //the safety requirements are described above each function
+
+use std::ffi::{CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::*;
+
+pub struct TodoHandle {
+    list: TodoList,
+}
+
+#[no_mangle]
+pub extern "C" fn todo_list_new() -> *mut TodoHandle {
+    Box::into_raw(Box::new(TodoHandle { list: TodoList::new() }))
+}
+
+//handle must have come from todo_list_new and not been freed already, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn todo_list_free(handle: *mut TodoHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+//runs one query line, returning its result lines joined with newlines
+//returns null if either pointer is null, the line isn't valid UTF-8, or the query fails (the error is printed to stderr)
+//a panic is caught rather than unwinding into the host, also giving null (the list may be left part way through the query)
+//handle must be a live list from todo_list_new, and line a nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn todo_run_line(handle: *mut TodoHandle, line: *const c_char) -> *mut c_char {
+    if handle.is_null() || line.is_null() {
+        return ptr::null_mut()
+    }
+    let line = match CStr::from_ptr(line).to_str() {
+        Ok(l) => l,
+        Err(_) => return ptr::null_mut(),
+    };
+    let list = &mut (*handle).list;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| runner::run_line(line, list)));
+    match result {
+        Ok(Some(r)) => match CString::new(r.to_lines(OutputFormat::Text).join("\n")) {
+            Ok(s) => s.into_raw(),
+            Err(_) => ptr::null_mut(), //results can't contain nul characters since queries can't
+        },
+        Ok(None) | Err(_) => ptr::null_mut(),
+    }
+}
+
+//s must have come from todo_run_line and not been freed already, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn todo_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //runs a line through the C interface, taking ownership of the result
+    unsafe fn run(handle: *mut TodoHandle, line: &str) -> Option<String> {
+        let line = CString::new(line).unwrap();
+        let result = todo_run_line(handle, line.as_ptr());
+        if result.is_null() {
+            return None
+        }
+        let text = CStr::from_ptr(result).to_str().unwrap().to_owned();
+        todo_string_free(result);
+        Some(text)
+    }
+
+    #[test]
+    fn run_lines_through_pointers() {
+        unsafe {
+            let handle = todo_list_new();
+            assert_eq!(run(handle, "add \"buy milk\" #shop").as_deref(), Some("0"));
+            assert_eq!(run(handle, "search milk").as_deref(), Some("1 item(s) found\n0 \"buy milk\" #shop"));
+            assert_eq!(run(handle, "done 7"), None);
+            assert_eq!(run(handle, "done 99999999999999999999999"), None); //too large to be an index
+            assert!(todo_run_line(handle, ptr::null()).is_null());
+            let line = CString::new("search milk").unwrap();
+            assert!(todo_run_line(ptr::null_mut(), line.as_ptr()).is_null());
+            todo_string_free(ptr::null_mut());
+            todo_list_free(handle);
+            todo_list_free(ptr::null_mut());
+        }
+    }
+}
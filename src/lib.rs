@@ -1,8 +1,11 @@
+pub mod matching;
 pub mod parser;
 pub mod query;
 pub mod runner;
 pub mod todo_list;
 pub mod trie;
+#[cfg(feature = "capi")]
+pub mod capi;
 
 pub mod essd;
 pub mod fast_trie;
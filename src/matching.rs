@@ -0,0 +1,148 @@
+//the word matching used by searches, for reuse elsewhere with the same semantics
+use crate::*;
+
+/// Whether the characters of subsequence appear in sequence in order, possibly with gaps between them.
+///
+/// ```
+/// use todo_swamp::matching::match_subsequence;
+/// assert!(match_subsequence("concatenate", "cat"));
+/// assert!(match_subsequence("concatenate", "cnn"));
+/// assert!(!match_subsequence("concatenate", "tac"));
+/// assert!(!match_subsequence("cat", "cats"));
+/// ```
+///
+/// An empty subsequence matches every sequence, even an empty one.
+///
+/// ```
+/// use todo_swamp::matching::match_subsequence;
+/// assert!(match_subsequence("cat", ""));
+/// assert!(match_subsequence("", ""));
+/// ```
+pub fn match_subsequence(sequence: &str, subsequence: &str) -> bool {
+    subsequence_matches(sequence, subsequence, false)
+}
+
+//compares ASCII bytes, either exactly or ignoring case
+fn same_byte(a: u8, b: u8, ignore_case: bool) -> bool {
+    if ignore_case { a.eq_ignore_ascii_case(&b) } else { a == b }
+}
+
+fn subsequence_matches(sequence: &str, subsequence: &str, ignore_case: bool) -> bool {
+    let mut remaining = subsequence.bytes().peekable(); //this only splits on exact characters when we're using ASCII, not unicode
+    for byte in sequence.bytes() {
+        match remaining.peek() {
+            None => return true,
+            Some(&next) if same_byte(byte, next, ignore_case) => {
+                remaining.next();
+            },
+            Some(_) => {},
+        }
+    }
+    remaining.peek().is_none()
+}
+
+//a subsequence match skipping at most max_gap characters between consecutive matched characters
+//the earliest match of a character isn't always the one which lets the rest match, so every position reached is kept
+pub fn match_gapped_subsequence(sequence: &str, subsequence: &str, max_gap: usize) -> bool {
+    gapped_subsequence_matches(sequence, subsequence, max_gap, false)
+}
+
+fn gapped_subsequence_matches(sequence: &str, subsequence: &str, max_gap: usize, ignore_case: bool) -> bool {
+    let seq_bytes = sequence.as_bytes();
+    let mut sub_bytes = subsequence.as_bytes().iter();
+    let mut reached: Vec<usize> = match sub_bytes.next() {
+        None => return true,
+        Some(first) => (0..seq_bytes.len()).filter(|&p| same_byte(seq_bytes[p], *first, ignore_case)).collect(),
+    };
+    for byte in sub_bytes {
+        reached = (0..seq_bytes.len())
+            .filter(|&p| same_byte(seq_bytes[p], *byte, ignore_case) && reached.iter().any(|&q| q < p && p - q - 1 <= max_gap))
+            .collect();
+        if reached.is_empty() {
+            return false
+        }
+    }
+    !reached.is_empty()
+}
+
+//whether the whole sequence matches a glob pattern, * matching any run of characters (including none) and ? exactly one
+//on a mismatch only the most recent * has to be extended, since any earlier one can be extended by it just as well
+pub fn match_glob(sequence: &str, pattern: &str) -> bool {
+    glob_matches(sequence, pattern, false)
+}
+
+fn glob_matches(sequence: &str, pattern: &str, ignore_case: bool) -> bool {
+    let sequence: Vec<char> = sequence.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut s, mut p) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None; //the pattern position after the last *, and where in sequence it started matching
+    while s < sequence.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                last_star = Some((p, s));
+            },
+            Some(c) if *c == '?' || *c == sequence[s] || (ignore_case && c.eq_ignore_ascii_case(&sequence[s])) => {
+                p += 1;
+                s += 1;
+            },
+            _ => match last_star {
+                Some((star_p, star_s)) => { //let the * match one more character
+                    p = star_p;
+                    s = star_s + 1;
+                    last_star = Some((star_p, s));
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+//matches a single word (or tag) against a search term as a search with that mode would
+//ignoring case only folds ASCII letters, like the rest of the matching which is on ASCII words
+pub fn match_word(sequence: &str, search: &str, mode: MatchMode, ignore_case: bool) -> bool {
+    match mode {
+        MatchMode::Subsequence => subsequence_matches(sequence, search, ignore_case),
+        MatchMode::MaxGap(max_gap) => gapped_subsequence_matches(sequence, search, max_gap, ignore_case),
+        MatchMode::Prefix if ignore_case => matches!(sequence.as_bytes().get(..search.len()), Some(start) if start.eq_ignore_ascii_case(search.as_bytes())),
+        MatchMode::Prefix => sequence.starts_with(search),
+        MatchMode::Exact if ignore_case => sequence.eq_ignore_ascii_case(search),
+        MatchMode::Exact => sequence == search,
+        MatchMode::Glob => glob_matches(sequence, search, ignore_case),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_gap() {
+        //0 is a substring match
+        assert!(match_gapped_subsequence("concatenate", "cat", 0));
+        assert!(!match_gapped_subsequence("cart", "cat", 0));
+        //2 allows up to two characters between matched ones
+        assert!(match_gapped_subsequence("cart", "cat", 2));
+        assert!(match_gapped_subsequence("coat", "cat", 2));
+        assert!(!match_gapped_subsequence("cabinet", "cat", 2));
+        //the t is too far from the first a, but not from the second
+        assert!(match_gapped_subsequence("caxaxxt", "cat", 2));
+        assert!(!match_gapped_subsequence("caxxxxt", "cat", 2));
+        assert!(match_gapped_subsequence("anything", "", 0));
+    }
+
+
+    #[test]
+    fn match_word_ignoring_case() {
+        for mode in [MatchMode::Subsequence, MatchMode::MaxGap(1), MatchMode::Prefix, MatchMode::Exact, MatchMode::Glob] {
+            assert!(match_word("Water", "Water", mode, false));
+            assert!(!match_word("Water", "WATER", mode, false));
+            assert!(match_word("Water", "WATER", mode, true));
+            assert!(!match_word("Water", "WINTER", mode, true));
+        }
+        assert!(match_word("Water", "wT", MatchMode::Subsequence, true));
+        assert!(match_word("Water", "wA", MatchMode::Prefix, true));
+        assert!(match_word("Water", "w?T*", MatchMode::Glob, true));
+    }
+}
@@ -1,67 +1,273 @@
+use std::fmt;
+
 use crate::*;
 
 use nom::{
     IResult,
-    bytes::complete::{tag, take_while1},
+    error::{ErrorKind, ParseError as NomParseError},
+    bytes::complete::{tag, take_while, take_while1, take_while_m_n, is_not},
     branch::alt,
-    character::complete::{one_of, digit1},
+    character::complete::{one_of, digit1, anychar},
     multi::{separated_nonempty_list, many0},
-    sequence::{pair, preceded, delimited},
-    combinator::opt,
+    sequence::{pair, preceded, delimited, terminated, tuple},
+    combinator::{opt, map, map_opt, rest, verify, not, recognize},
 };
 
+//Options changing what the parsers accept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParserConfig {
+    pub separators: Vec<char>, //characters which also separate the words of a description (besides a space)
+    pub any_field: bool, //search terms without a '#' match tags as well as description words
+    pub tag_policy: TagPolicy, //whether search tags match exactly (the default) or like words
+    //search terms with fewer characters are rejected, since very short ones match almost everything (a bare # is still allowed)
+    pub min_term_len: usize,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig {
+            separators: Vec::new(),
+            any_field: false,
+            tag_policy: TagPolicy::default(),
+            min_term_len: 1,
+        }
+    }
+}
+
+//Errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(pub String);
+impl<'a> NomParseError<&'a str> for ParseError {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        ParseError(format!("expected {:?} at \"{}\"", kind, input))
+    }
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "An error occurred while parsing the query: {}.", self.0)
+    }
+}
+
 //Specification parsers
 
 /*Assumptions:
 -Whitespace between tokens in the specification grammar represent a single space or tab character (as parsed by the 'ws' function below)
 -Whitespace is not required following a <description> if the add query's list of <tags> is empty
 -<description>s consist of at least one <word>
+-a <word> can start with # when it's escaped like \#1 (digits are allowed after the #), it's stored without the backslash
+ a search <word> is escaped the same way to find it, a bare \# is the word "#"
+-<word>s in a <description> are separated by a single space, or one of the ParserConfig separators (optionally followed by a space)
+-a search <word> or <tag> can be given its own match mode like exact:deploy or prefix:#work, overriding the search's mode for that term
+-search modifiers start with a colon, like :prefix, :exact, :glob, :all, :any, :untagged, :include-done and :not (and :max-gap, :offset or :limit followed by a number)
+ a colon can't start a search <word>, so every <word> (like "not" or "any") is a plain term, and an unknown modifier is an error
+-only the first word of a query picks the command, so a search <word> like "done", "add" or "search" is a plain term and doesn't need quoting
+-":all-tags" or ":any-tags" combine the search <tag>s separately from the <word>s (which ":all" or ":any" still combine), an item has to match both
+-":not" negates the whole search (the terms as combined by :all or :any), the other modifiers still apply to its results
+-":order-by" followed by "length" or "length-desc" orders the results by their number of description words (before :offset and :limit)
+-":and-not" followed by a <word> or <tag> leaves out items matching it, it has to come after some other search term
+ there's no negation of a single term, so "#bug :and-not #wontfix" finds the items found by both "#bug" and ":not #wontfix"
+-with the ":glob" modifier, * and ? in search <word>s match any run of characters and any single character, and are not allowed otherwise
+-a search <tag> of just # (no text) matches any item with at least one tag
+-search <word>s and <tag>s must have at least ParserConfig::min_term_len characters (1 by default)
+-search <tag>s match item tags exactly by default, rather than as subsequences like words (see ParserConfig::tag_policy)
+-<tag>s may contain / separating the levels of a hierarchy, a search <tag> ending in / like #work/ matches every tag under it
+-<tag>s may contain uppercase letters, they're kept for display but tags are matched ignoring case
+-a note is the text between double quotes (which can't contain one), an edit query's note "" removes the item's note
+-<tag>s in add, retag and search queries may be quoted like #"in progress" to include spaces, the quotes are not part of the tag
 */
 
-pub fn query(input : &str) -> IResult<&str, Query> {
-    alt((add, done, search))(input)
+//parses a whole query, flattening nom's error wrapper
+pub fn parse(input : &str, config : &ParserConfig) -> Result<Query, ParseError> {
+    match query_with(input, config) {
+        Ok((_, q)) => Ok(q),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(e),
+        Err(nom::Err::Incomplete(_)) => Err(ParseError(String::from("incomplete input"))), //complete parsers are used throughout, so this does not happen
+    }
+}
+
+pub fn query(input : &str) -> IResult<&str, Query, ParseError> {
+    query_with(input, &ParserConfig::default())
 }
 
-fn add(input : &str) -> IResult<&str, Query> {
-    match preceded(
+pub fn query_with<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, Query, ParseError> {
+    alt((|i| add(i, config), done, done_list, toggle, set_done, retag, clear_tags, |i| edit(i, config), bump, swap, show, reset, |i| search(i, config), search_note, next_index))(input)
+}
+
+//the modifiers after the tags can be given in any order, but each at most once
+fn add<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, Query, ParseError> {
+    let (rest, ((d, ts), modifiers)) = preceded(
         pair(tag("add"), ws),
-        pair(delimited(tag("\""), description, tag("\"")), tags)
+        pair(pair(delimited(tag("\""), |i| description(i, config), tag("\"")), tags), many0(preceded(ws, add_modifier)))
+    )(input)?;
+    if !rest.trim_end().is_empty() {
+        return Err(nom::Err::Failure(ParseError(format!("unknown or malformed add modifier at \"{}\"", rest.trim_start()))))
+    }
+    let mut options = AddOptions::default();
+    let mut seen = Vec::new();
+    for (modifier, name) in modifiers {
+        if seen.contains(&name) {
+            return Err(nom::Err::Failure(ParseError(format!("duplicate add modifier \"{}\"", name))))
+        }
+        seen.push(name);
+        match modifier {
+            AddModifier::Priority(p) => options.priority = Some(p),
+            AddModifier::Due(date) => options.due = Some(date),
+            AddModifier::Note(n) => options.note = n,
+        }
+    }
+    Ok((rest, Query::Add(d, ts, options)))
+}
+
+fn done(input : &str) -> IResult<&str, Query, ParseError> {
+    match preceded(
+        pair(tag("done"), ws),
+        pair(index, opt(preceded(ws, note)))
     )(input) {
         Err(e) => Err(e),
-        Ok((rest, (d, ts))) => Ok((rest, Query::Add(d, ts))),
+        Ok((rest, (i, n))) => Ok((rest, Query::Done(i, n.flatten()))),
     }
 }
 
-fn done(input : &str) -> IResult<&str, Query> {
+fn done_list(input : &str) -> IResult<&str, Query, ParseError> {
+    whole_line("done-list")(input).map(|(rest, _)| (rest, Query::DoneList))
+}
+
+fn next_index(input : &str) -> IResult<&str, Query, ParseError> {
+    whole_line("next-index")(input).map(|(rest, _)| (rest, Query::NextIndex))
+}
+
+fn toggle(input : &str) -> IResult<&str, Query, ParseError> {
     match preceded(
-        pair(tag("done"), ws),
+        pair(tag("toggle"), ws),
         index
     )(input) {
         Err(e) => Err(e),
-        Ok((rest, i)) => Ok((rest, Query::Done(i))),
+        Ok((rest, i)) => Ok((rest, Query::Toggle(i))),
+    }
+}
+
+//anything other than true or false after the index is an error, rather than trying the other queries
+fn set_done(input : &str) -> IResult<&str, Query, ParseError> {
+    let (rest, i) = preceded(pair(tag("set-done"), ws), index)(input)?;
+    match preceded(ws, alt((map(tag("true"), |_| true), map(tag("false"), |_| false))))(rest) {
+        Ok((rest, done)) => Ok((rest, Query::SetDone(i, done))),
+        Err(_) => Err(nom::Err::Failure(ParseError(format!("expected true or false at \"{}\"", rest.trim_start())))),
+    }
+}
+
+fn retag(input : &str) -> IResult<&str, Query, ParseError> {
+    match preceded(
+        pair(tag("retag"), ws),
+        tuple((index, preceded(ws, todo_tag), preceded(ws, todo_tag)))
+    )(input) {
+        Err(e) => Err(e),
+        Ok((rest, (i, old, new))) => Ok((rest, Query::Retag(i, old, new))),
     }
 }
 
-fn search(input : &str) -> IResult<&str, Query> {
+//a note after the description replaces the item's note, note "" removes it
+fn edit<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, Query, ParseError> {
+    match preceded(
+        pair(tag("edit"), ws),
+        tuple((index, preceded(ws, delimited(tag("\""), |i| description(i, config), tag("\""))), opt(preceded(ws, note))))
+    )(input) {
+        Err(e) => Err(e),
+        Ok((rest, (i, d, n))) => Ok((rest, Query::Edit(i, d, n))),
+    }
+}
+
+fn clear_tags(input : &str) -> IResult<&str, Query, ParseError> {
+    match preceded(
+        pair(tag("clear-tags"), ws),
+        index
+    )(input) {
+        Err(e) => Err(e),
+        Ok((rest, i)) => Ok((rest, Query::ClearTags(i))),
+    }
+}
+
+fn bump(input : &str) -> IResult<&str, Query, ParseError> {
+    match preceded(
+        pair(tag("bump"), ws),
+        pair(index, preceded(ws, delta))
+    )(input) {
+        Err(e) => Err(e),
+        Ok((rest, (i, d))) => Ok((rest, Query::Bump(i, d))),
+    }
+}
+
+fn swap(input : &str) -> IResult<&str, Query, ParseError> {
+    match preceded(
+        pair(tag("swap"), ws),
+        pair(index, preceded(ws, index))
+    )(input) {
+        Err(e) => Err(e),
+        Ok((rest, (a, b))) => Ok((rest, Query::Swap(a, b))),
+    }
+}
+
+fn show(input : &str) -> IResult<&str, Query, ParseError> {
+    match preceded(
+        pair(tag("show"), ws),
+        separated_nonempty_list(tag(" "), index)
+    )(input) {
+        Err(e) => Err(e),
+        Ok((rest, idxs)) => Ok((rest, Query::Show(idxs))),
+    }
+}
+
+//nothing else may follow (besides trailing whitespace), so a line which only starts with reset can't empty the list
+fn reset(input : &str) -> IResult<&str, Query, ParseError> {
+    whole_line("reset")(input).map(|(rest, _)| (rest, Query::Reset))
+}
+
+//a command without arguments, which has to be the whole line apart from trailing whitespace
+fn whole_line<'a>(name : &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, ParseError> {
+    terminated(tag(name), pair(take_while(|c| c == ' ' || c == '\t'), not(anychar)))
+}
+
+fn search<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, Query, ParseError> {
     match preceded(
         pair(tag("search"), ws),
-        search_query
+        |i| search_query(i, config)
     )(input) {
         Err(e) => Err(e),
         Ok((rest, p)) => Ok((rest, Query::Search(p))),
     }
 }
 
-fn description(input : &str) -> IResult<&str, Vec<Word>> {
-    separated_nonempty_list(tag(" "), word)(input)
+//takes the remainder of the line as the text to look for
+fn search_note(input : &str) -> IResult<&str, Query, ParseError> {
+    match preceded(
+        pair(tag("search-note"), ws),
+        verify(rest, |text: &str| !text.is_empty())
+    )(input) {
+        Err(e) => Err(e),
+        Ok((rest, text)) => Ok((rest, Query::SearchNote(text.to_string()))),
+    }
+}
+
+fn description<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, Vec<Word>, ParseError> {
+    separated_nonempty_list(|i| separator(i, config), word)(input)
 }
 
-fn word(input : &str) -> IResult<&str, Word> {
-    prim_word(input).map(|(rest, w)| (rest, Word::new(w)))
+fn word(input : &str) -> IResult<&str, Word, ParseError> {
+    alt((hash_word, map_opt(prim_word, Word::new)))(input)
+}
+
+//a word starting with # is escaped like \#1, so it isn't taken for a tag, the rest of it can have digits as well
+fn hash_word(input : &str) -> IResult<&str, Word, ParseError> {
+    map_opt(
+        preceded(tag("\\"), recognize(pair(tag("#"), take_while(|c: char| is_lowecase_or_dash(c) || c.is_ascii_digit())))),
+        Word::new
+    )(input)
 }
 
 //allows a single tab character (or a space) before first tag for the sake of consistency
-fn tags(input: &str) -> IResult<&str, Vec<Tag>> { 
+fn tags(input: &str) -> IResult<&str, Vec<Tag>, ParseError> { 
     match opt(pair(preceded(ws, todo_tag), many0(preceded(tag(" "), todo_tag))))(input) {
         Err(e) => Err(e),
         Ok((rest, ts)) => Ok((rest, match ts {
@@ -74,37 +280,482 @@ fn tags(input: &str) -> IResult<&str, Vec<Tag>> {
     }
 }
 
-fn todo_tag(input : &str) -> IResult<&str, Tag> {
-    preceded(tag("#"), prim_word)(input).map(|(rest, w)| (rest, Tag::new(w)))
+fn todo_tag(input : &str) -> IResult<&str, Tag, ParseError> {
+    preceded(tag("#"), tag_text)(input).map(|(rest, w)| (rest, Tag::new(w)))
+}
+
+//either a plain word, or words in quotes separated by spaces like "in progress" (just spaces isn't a tag)
+//either can contain / to make a hierarchy of tags like work/project/task
+fn tag_text(input : &str) -> IResult<&str, &str, ParseError> {
+    alt((
+        delimited(tag("\""), verify(take_while1(|c| is_tag_char(c) || c == ' '), |t: &str| !t.trim().is_empty()), tag("\"")),
+        take_while1(is_tag_char),
+    ))(input)
+}
+fn is_tag_char(c : char) -> bool {
+    is_lowecase_or_dash(c) || c.is_ascii_uppercase() || c == '/'
+}
+
+enum AddModifier {
+    Priority (u8),
+    Due (Date),
+    Note (Option<String>),
+}
+fn add_modifier(input : &str) -> IResult<&str, (AddModifier, &'static str), ParseError> {
+    alt((
+        map(preceded(pair(tag("priority"), ws), map_opt(digit1, |v: &str| v.parse().ok())), |p| (AddModifier::Priority(p), "priority")),
+        map(preceded(pair(tag("due"), ws), date), |d| (AddModifier::Due(d), "due")),
+        map(note, |n| (AddModifier::Note(n), "note")),
+    ))(input)
+}
+
+//a date like 2024-01-31, which has to exist
+fn date(input : &str) -> IResult<&str, Date, ParseError> {
+    map_opt(
+        tuple((digits(4), preceded(tag("-"), digits(2)), preceded(tag("-"), digits(2)))),
+        |(y, m, d)| Date::new(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?)
+    )(input)
+}
+fn digits<'a>(n : usize) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, ParseError> {
+    take_while_m_n(n, n, |c: char| c.is_ascii_digit())
 }
 
-fn index(input : &str) -> IResult<&str, Index> {
-    digit1(input).map(|(rest, v)| (rest, Index::new(v.parse().unwrap())))
+//an empty note like note "" is no note at all
+fn note(input : &str) -> IResult<&str, Option<String>, ParseError> {
+    preceded(
+        pair(tag("note"), ws),
+        delimited(tag("\""), opt(is_not("\"")), tag("\""))
+    )(input).map(|(rest, n)| (rest, n.map(str::to_string)))
 }
 
-fn search_query(input : &str) -> IResult<&str, SearchParams> {
-    separated_nonempty_list(tag(" "), search_word_or_tag)(input).map(|(rest, p)| (rest, SearchParams{params: p}))
+//a number too large for an index doesn't parse
+fn index(input : &str) -> IResult<&str, Index, ParseError> {
+    map(map_opt(digit1, |v: &str| v.parse().ok()), Index::new)(input)
+}
+
+//a signed number like +3 or -12, large values are clamped since priorities saturate anyway
+fn delta(input : &str) -> IResult<&str, i32, ParseError> {
+    pair(one_of("+-"), digit1)(input).map(|(rest, (sign, v))| {
+        let magnitude = v.parse::<i32>().unwrap_or(i32::MAX).min(u8::MAX as i32);
+        (rest, if sign == '-' { -magnitude } else { magnitude })
+    })
+}
+
+//terms and modifiers can be given in any order, e.g. "search foo :offset 10 :limit 5 #bar"
+//giving two different match (or combine) modifiers is an error rather than one silently winning
+fn search_query<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, SearchParams, ParseError> {
+    let (rest, tokens) = separated_nonempty_list(tag(" "), |i| search_token(i, config))(input)?;
+    let mut sp = SearchParams::new(Vec::new()).with_tag_policy(config.tag_policy);
+    let mut mode: Option<(MatchMode, &str)> = None;
+    let mut combine: Option<(CombineMode, &str)> = None;
+    let mut tag_combine: Option<(CombineMode, &str)> = None;
+    let mut order: Option<(ResultOrder, &str)> = None;
+    for token in tokens {
+        match token {
+            SearchToken::Term(p) => sp.params.push(p),
+            SearchToken::Exclude(_) if sp.params.is_empty() => {
+                return Err(nom::Err::Failure(ParseError(String::from("\":and-not\" needs a search term before it"))))
+            },
+            SearchToken::Exclude(p) => sp.excluded.push(p),
+            SearchToken::Offset(n) => sp.offset = n,
+            SearchToken::Limit(n) => sp.limit = Some(n),
+            SearchToken::Untagged => sp.untagged = true,
+            SearchToken::IncludeDone => sp.include_done = true,
+            SearchToken::Negate => sp.negate = true,
+            SearchToken::Mode(m, name) => match mode {
+                Some((previous, previous_name)) if previous != m => return Err(conflict(previous_name, name)),
+                _ => mode = Some((m, name)),
+            },
+            SearchToken::Combine(c, name) => match combine {
+                Some((previous, previous_name)) if previous != c => return Err(conflict(previous_name, name)),
+                _ => combine = Some((c, name)),
+            },
+            SearchToken::TagCombine(c, name) => match tag_combine {
+                Some((previous, previous_name)) if previous != c => return Err(conflict(previous_name, name)),
+                _ => tag_combine = Some((c, name)),
+            },
+            SearchToken::Order(o, name) => match order {
+                Some((previous, previous_name)) if previous != o => return Err(conflict(previous_name, name)),
+                _ => order = Some((o, name)),
+            },
+        }
+    }
+    if let Some((m, _)) = mode {
+        sp.mode = m;
+    }
+    let has_wildcards = |s: &str| s.contains(['*', '?']);
+    let unglobbed_wildcards = |p: &SearchWordOrTag| match p.split_mode() {
+        (SearchWordOrTag::RawWord(s), own_mode) | (SearchWordOrTag::RawAny(s), own_mode) => own_mode.unwrap_or(sp.mode) != MatchMode::Glob && has_wildcards(s),
+        _ => false,
+    };
+    if sp.params.iter().chain(&sp.excluded).any(unglobbed_wildcards) {
+        return Err(nom::Err::Failure(ParseError(String::from("search wildcards * and ? need the :glob modifier"))))
+    }
+    let short_term = sp.params.iter().chain(&sp.excluded).find_map(|p| match p.split_mode().0 {
+        SearchWordOrTag::RawTag(t) if t.is_empty() => None,
+        SearchWordOrTag::RawWord(s) | SearchWordOrTag::RawTag(s) | SearchWordOrTag::RawAny(s) => Some(s).filter(|s| s.chars().count() < config.min_term_len),
+        SearchWordOrTag::WithMode(..) => None,
+    });
+    if let Some(s) = short_term {
+        return Err(nom::Err::Failure(ParseError(format!("search term \"{}\" is shorter than the minimum of {} character(s)", s, config.min_term_len))))
+    }
+    if let Some((c, _)) = combine {
+        sp.combine = c;
+    }
+    if let Some((c, _)) = tag_combine {
+        sp.tag_combine = Some(c);
+    }
+    if let Some((o, _)) = order {
+        sp.order = o;
+    }
+    Ok((rest, sp))
 }
 
 //Helper parsers and functions
-fn search_word_or_tag(input : &str) -> IResult<&str, SearchWordOrTag> {
-    match alt((pair(tag("#"), prim_word), pair(tag(""), prim_word)))(input) {
+enum SearchToken<'a> {
+    Term (SearchWordOrTag),
+    Exclude (SearchWordOrTag),
+    Offset (usize),
+    Limit (usize),
+    Mode (MatchMode, &'a str),
+    Combine (CombineMode, &'a str),
+    TagCombine (CombineMode, &'a str),
+    Order (ResultOrder, &'a str),
+    Untagged,
+    IncludeDone,
+    Negate,
+}
+//modifiers start with a colon, which search words can't, so a word like "any" or "limit" is always a search term
+fn search_token<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, SearchToken<'a>, ParseError> {
+    alt((
+        map(|i| moded_search_term(i, config), SearchToken::Term),
+        map(preceded(pair(modifier("offset"), ws), number), SearchToken::Offset),
+        map(preceded(pair(modifier("limit"), ws), number), SearchToken::Limit),
+        map(preceded(pair(modifier("max-gap"), ws), number), |k| SearchToken::Mode(MatchMode::MaxGap(k), ":max-gap")),
+        map(modifier("prefix"), |name| SearchToken::Mode(MatchMode::Prefix, name)),
+        map(modifier("exact"), |name| SearchToken::Mode(MatchMode::Exact, name)),
+        map(modifier("glob"), |name| SearchToken::Mode(MatchMode::Glob, name)),
+        map(modifier("all"), |name| SearchToken::Combine(CombineMode::All, name)),
+        map(modifier("any"), |name| SearchToken::Combine(CombineMode::Any, name)),
+        map(modifier("all-tags"), |name| SearchToken::TagCombine(CombineMode::All, name)),
+        map(modifier("any-tags"), |name| SearchToken::TagCombine(CombineMode::Any, name)),
+        map(modifier("untagged"), |_| SearchToken::Untagged),
+        map(modifier("include-done"), |_| SearchToken::IncludeDone),
+        map(modifier("not"), |_| SearchToken::Negate),
+        map(preceded(pair(modifier("order-by"), ws), word_only("length-desc")), |_| SearchToken::Order(ResultOrder::LengthDescending, ":order-by length-desc")),
+        map(preceded(pair(modifier("order-by"), ws), word_only("length")), |_| SearchToken::Order(ResultOrder::Length, ":order-by length")),
+        map(preceded(pair(modifier("and-not"), ws), |i| search_term(i, config)), SearchToken::Exclude),
+        unknown_modifier,
+        map(|i| search_word_or_tag(i, config), SearchToken::Term),
+    ))(input)
+}
+//a colon and then the name, giving both (like ":any") for naming the modifier in errors
+fn modifier<'a>(name : &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, ParseError> {
+    recognize(preceded(tag(":"), word_only(name)))
+}
+//the name as a whole word, so "length" isn't taken from the start of "length-desc"
+fn word_only<'a>(name : &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str, ParseError> {
+    terminated(tag(name), not(take_while1(is_search_word_char)))
+}
+//any other colon is a mistake (like a misspelt modifier) rather than the end of the search
+fn unknown_modifier<'a>(input : &'a str) -> IResult<&'a str, SearchToken<'a>, ParseError> {
+    let (_, name) = recognize(preceded(tag(":"), take_while(is_search_word_char)))(input)?;
+    Err(nom::Err::Failure(ParseError(format!("unknown search modifier \"{}\"", name))))
+}
+fn conflict(first : &str, second : &str) -> nom::Err<ParseError> {
+    nom::Err::Failure(ParseError(format!("conflicting search modifiers \"{}\" and \"{}\"", first, second)))
+}
+fn number(input : &str) -> IResult<&str, usize, ParseError> {
+    map_opt(digit1, |v: &str| v.parse().ok())(input)
+}
+fn search_term<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, SearchWordOrTag, ParseError> {
+    alt((|i| moded_search_term(i, config), |i| search_word_or_tag(i, config)))(input)
+}
+//a word or tag with a mode of its own like exact:deploy, there's no per-term max-gap
+fn moded_search_term<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, SearchWordOrTag, ParseError> {
+    let term_mode = alt((
+        map(tag("subsequence:"), |_| MatchMode::Subsequence),
+        map(tag("prefix:"), |_| MatchMode::Prefix),
+        map(tag("exact:"), |_| MatchMode::Exact),
+        map(tag("glob:"), |_| MatchMode::Glob),
+    ));
+    map(pair(term_mode, |i| search_word_or_tag(i, config)), |(mode, term)| SearchWordOrTag::WithMode(mode, Box::new(term)))(input)
+}
+fn search_word_or_tag<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, SearchWordOrTag, ParseError> {
+    let escaped_word = preceded(tag("\\"), recognize(pair(tag("#"), take_while(is_search_word_char)))); //like \#1 in a description
+    match alt((pair(tag("#"), tag_text), pair(tag("#"), tag("")), pair(tag(""), escaped_word), pair(tag(""), take_while1(is_search_word_char))))(input) {
         Err(e) => Err(e),
         Ok((rest, (hash, wot))) => {
             if hash.starts_with("#") {
-                Ok( (rest, SearchWordOrTag::RawTag(wot.to_string())) )
+                Ok( (rest, SearchWordOrTag::RawTag(wot.to_lowercase())) ) //item tags are matched on their lowercased key
+            } else if config.any_field {
+                Ok( (rest, SearchWordOrTag::RawAny(wot.to_string())) )
             } else {
                 Ok( (rest, SearchWordOrTag::RawWord(wot.to_string())) )
             }
         }
     }
 }
-fn prim_word(input : &str) -> IResult<&str, &str> {
+fn prim_word(input : &str) -> IResult<&str, &str, ParseError> {
     take_while1(is_lowecase_or_dash)(input)
 }
 fn is_lowecase_or_dash(c : char) -> bool {
     c.is_ascii_lowercase() || c == '-'
 }
-fn ws(input : &str) -> IResult<&str, char> { 
+//search words can also contain glob wildcards, which search_query only accepts along with the glob modifier
+//and the # and digits of escaped words, a term can only start with # when it's escaped like them
+fn is_search_word_char(c : char) -> bool {
+    is_lowecase_or_dash(c) || c.is_ascii_digit() || c == '#' || c == '*' || c == '?'
+}
+//a single space, or one of the configured separators optionally followed by a space (so "a, b" is split like "a,b")
+fn separator<'a>(input : &'a str, config : &ParserConfig) -> IResult<&'a str, (), ParseError> {
+    alt((
+        map(pair(verify(anychar, |c| config.separators.contains(c)), opt(tag(" "))), |_| ()),
+        map(tag(" "), |_| ()),
+    ))(input)
+}
+fn ws(input : &str) -> IResult<&str, char, ParseError> { 
     one_of(" \t")(input)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(input: &str) -> Query {
+        parse(input, &ParserConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn empty_note() {
+        match parsed("add \"buy milk\" note \"\"") {
+            Query::Add(_, _, options) => assert_eq!(options.note, None),
+            q => panic!("parsed as {:?}", q),
+        }
+        match parsed("edit 3 \"buy milk\" note \"\"") {
+            Query::Edit(i, _, note) => assert_eq!((i, note), (Index::new(3), Some(None))),
+            q => panic!("parsed as {:?}", q),
+        }
+        match parsed("edit 3 \"buy milk\" note \"semi skimmed\"") {
+            Query::Edit(_, _, note) => assert_eq!(note, Some(Some(String::from("semi skimmed")))),
+            q => panic!("parsed as {:?}", q),
+        }
+        assert_eq!(parsed("done 3 note \"\""), Query::Done(Index::new(3), None));
+    }
+
+    #[test]
+    fn reset_is_whole_line() {
+        assert_eq!(parsed("reset"), Query::Reset);
+        assert_eq!(parsed("reset "), Query::Reset);
+        assert!(parse("resets", &ParserConfig::default()).is_err());
+        assert!(parse("reset everything", &ParserConfig::default()).is_err());
+    }
+
+    fn search_params(input: &str) -> SearchParams {
+        match parsed(input) {
+            Query::Search(sp) => sp,
+            q => panic!("parsed as {:?}", q),
+        }
+    }
+
+    fn error(input: &str) -> String {
+        parse(input, &ParserConfig::default()).unwrap_err().0
+    }
+
+    #[test]
+    fn conflicting_modifiers() {
+        for (a, b) in [(":prefix", ":exact"), (":exact", ":glob"), (":all", ":any"), (":all-tags", ":any-tags"), (":order-by length", ":order-by length-desc")] {
+            assert_eq!(error(&format!("search foo {} {}", a, b)), format!("conflicting search modifiers \"{}\" and \"{}\"", a, b));
+        }
+        assert_eq!(error("search foo :glob :max-gap 2"), "conflicting search modifiers \":glob\" and \":max-gap\"");
+        //the same modifier twice isn't a conflict
+        assert_eq!(search_params("search foo :any :any").combine, CombineMode::Any);
+        assert_eq!(search_params("search foo :exact bar :exact").mode, MatchMode::Exact);
+    }
+
+    #[test]
+    fn modifier_words_are_terms() {
+        let sp = search_params("search all any prefix exact glob not untagged include-done limit offset");
+        let terms: Vec<SearchWordOrTag> = ["all", "any", "prefix", "exact", "glob", "not", "untagged", "include-done", "limit", "offset"].iter()
+            .map(|w| SearchWordOrTag::RawWord(w.to_string()))
+            .collect();
+        assert_eq!(sp, SearchParams::new(terms));
+        let sp = search_params("search not :not :limit 2");
+        assert_eq!((sp.params, sp.negate, sp.limit), (vec![SearchWordOrTag::RawWord(String::from("not"))], true, Some(2)));
+        assert_eq!(error("search foo :bogus"), "unknown search modifier \":bogus\"");
+    }
+
+    #[test]
+    fn any_field_config() {
+        let config = ParserConfig { any_field: true, ..ParserConfig::default() };
+        match parse("search shop #home", &config).unwrap() {
+            Query::Search(sp) => assert_eq!(sp.params, vec![SearchWordOrTag::RawAny(String::from("shop")), SearchWordOrTag::RawTag(String::from("home"))]),
+            q => panic!("parsed as {:?}", q),
+        }
+    }
+
+    #[test]
+    fn quoted_tags() {
+        let add = parsed("add \"review pr\" #\"in progress\" #work");
+        assert_eq!(add, Query::Add(vec![Word::new("review").unwrap(), Word::new("pr").unwrap()], Tag::from_strings(["in progress", "work"]), AddOptions::default()));
+        //displayed quoted, and parsed back the same
+        let item = TodoItem::new(Index::new(0), vec![Word::new("review").unwrap()], Tag::from_strings(["in progress"]), false);
+        assert_eq!(item.to_string(), "0 \"review\" #\"in progress\"");
+        assert_eq!(parsed(&format!("add {}", &item.to_string()[2..])), Query::Add(item.description, item.tags, AddOptions::default()));
+        assert!(parse("add \"review\" #\" \"", &ParserConfig::default()).is_err());
+        assert!(parse("add \"review\" #\"\"", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn add_modifiers_in_any_order() {
+        let expected = AddOptions { priority: Some(3), due: Date::new(2024, 1, 1), note: Some(String::from("y")) };
+        for input in [
+            "add \"x\" #a priority 3 due 2024-01-01 note \"y\"",
+            "add \"x\" #a note \"y\" priority 3 due 2024-01-01",
+            "add \"x\" #a due 2024-01-01 note \"y\" priority 3",
+        ] {
+            assert_eq!(parsed(input), Query::Add(vec![Word::new("x").unwrap()], Tag::from_strings(["a"]), expected.clone()));
+        }
+        match parsed("add \"x\" due 2024-02-29") {
+            Query::Add(_, tags, options) => assert_eq!((tags, options.due), (Vec::new(), Date::new(2024, 2, 29))),
+            q => panic!("parsed as {:?}", q),
+        }
+        assert_eq!(error("add \"x\" #a priority 3 urgent"), "unknown or malformed add modifier at \"urgent\"");
+        assert_eq!(error("add \"x\" priority 3 priority 4"), "duplicate add modifier \"priority\"");
+        assert!(parse("add \"x\" due 2023-02-29", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        assert_eq!(parsed("done 18446744073709551615"), Query::Done(Index::new(u64::MAX), None));
+        assert!(parse("done 18446744073709551616", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn set_done_literals() {
+        assert_eq!(parsed("set-done 3 true"), Query::SetDone(Index::new(3), true));
+        assert_eq!(parsed("set-done 3 false"), Query::SetDone(Index::new(3), false));
+        assert_eq!(error("set-done 3 yes"), "expected true or false at \"yes\"");
+        assert_eq!(error("set-done 3"), "expected true or false at \"\"");
+    }
+
+    #[test]
+    fn bare_hash_is_empty_tag() {
+        assert_eq!(search_params("search #").params, vec![SearchWordOrTag::RawTag(String::new())]);
+        assert_eq!(search_params("search milk #").params, vec![SearchWordOrTag::RawWord("milk".to_string()), SearchWordOrTag::RawTag(String::new())]);
+    }
+
+    #[test]
+    fn glob_modifier() {
+        let sp = search_params("search :glob dep* d?ploy");
+        assert_eq!(sp.mode, MatchMode::Glob);
+        assert_eq!(sp.params, vec![SearchWordOrTag::RawWord("dep*".to_string()), SearchWordOrTag::RawWord("d?ploy".to_string())]);
+    }
+
+    #[test]
+    fn minimum_term_length() {
+        let config = ParserConfig { min_term_len: 3, ..ParserConfig::default() };
+        assert_eq!(parse("search wa", &config).unwrap_err().0, "search term \"wa\" is shorter than the minimum of 3 character(s)");
+        assert!(parse("search wat", &config).is_ok());
+        assert!(parse("search wat #ab", &config).is_err());
+        assert!(parse("search wat :not ab", &config).is_err()); //excluded terms are checked too
+        assert!(parse("search wat #", &config).is_ok()); //a bare # is any tag
+        assert!(parse("search a", &ParserConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn and_not_terms() {
+        let sp = search_params("search #bug :and-not #wontfix :and-not exact:flaky");
+        assert_eq!(sp.params, vec![SearchWordOrTag::RawTag(String::from("bug"))]);
+        assert_eq!(sp.excluded, vec![
+            SearchWordOrTag::RawTag(String::from("wontfix")),
+            SearchWordOrTag::WithMode(MatchMode::Exact, Box::new(SearchWordOrTag::RawWord(String::from("flaky")))),
+        ]);
+        assert!(!sp.negate);
+        assert_eq!(error("search :and-not #wontfix #bug"), "\":and-not\" needs a search term before it");
+        assert!(parse("search #bug :and-not", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn escaped_hash_words() {
+        match parsed("add \"text \\#1\" #issue") {
+            Query::Add(description, tags, _) => {
+                assert_eq!(description, vec![Word::new("text").unwrap(), Word::new("#1").unwrap()]);
+                assert_eq!(tags, vec![Tag::new("issue")]);
+            },
+            q => panic!("parsed as {:?}", q),
+        }
+        assert_eq!(search_params("search \\#1 #issue").params, vec![
+            SearchWordOrTag::RawWord(String::from("#1")),
+            SearchWordOrTag::RawTag(String::from("issue")),
+        ]);
+        assert_eq!(search_params("search \\#").params, vec![SearchWordOrTag::RawWord(String::from("#"))]);
+        assert_eq!(search_params("search 12").params, vec![SearchWordOrTag::RawWord(String::from("12"))]);
+        assert!(parse("search \\word", &ParserConfig::default()).is_err()); //only a # is escaped
+    }
+
+    #[test]
+    fn per_term_modes() {
+        let sp = search_params("search exact:deploy prefix:#prod stage :glob");
+        assert_eq!(sp.params, vec![
+            SearchWordOrTag::WithMode(MatchMode::Exact, Box::new(SearchWordOrTag::RawWord(String::from("deploy")))),
+            SearchWordOrTag::WithMode(MatchMode::Prefix, Box::new(SearchWordOrTag::RawTag(String::from("prod")))),
+            SearchWordOrTag::RawWord(String::from("stage")),
+        ]);
+        assert_eq!(sp.mode, MatchMode::Glob);
+    }
+
+    #[test]
+    fn command_words_are_terms() {
+        let commands = ["done", "add", "search", "toggle", "reset", "edit", "swap", "show", "done-list"];
+        let sp = search_params(&format!("search {} :any :limit 3", commands.join(" ")));
+        assert_eq!(sp.params, commands.iter().map(|w| SearchWordOrTag::RawWord(w.to_string())).collect::<Vec<_>>());
+        assert_eq!((sp.combine, sp.limit), (CombineMode::Any, Some(3)));
+        assert_eq!(search_params("search :include-done done").params, vec![SearchWordOrTag::RawWord(String::from("done"))]);
+        assert_eq!(parsed("search done"), Query::Search(SearchParams::new(vec![SearchWordOrTag::RawWord(String::from("done"))])));
+    }
+
+    #[test]
+    fn done_list_is_whole_line() {
+        assert_eq!(parsed("done-list"), Query::DoneList);
+        assert_eq!(parsed("done-list \t"), Query::DoneList);
+        assert!(parse("done-listxyz", &ParserConfig::default()).is_err());
+        assert!(parse("done-list 5", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn description_separators() {
+        let added = |input: &str, config: &ParserConfig| match parse(input, config).unwrap() {
+            Query::Add(description, _, _) => description,
+            q => panic!("parsed as {:?}", q),
+        };
+        let config = ParserConfig { separators: vec![',', ';'], ..ParserConfig::default() };
+        let abc = vec![Word::new("a").unwrap(), Word::new("b").unwrap(), Word::new("c").unwrap()];
+        assert_eq!(added("add \"a,b,c\"", &config), abc);
+        assert_eq!(added("add \"a, b;c\"", &config), abc);
+        assert_eq!(added("add \"a b c\"", &config), abc);
+        let item = TodoItem::new(Index::new(0), added("add \"a,b,c\"", &config), Vec::new(), false);
+        assert_eq!(item.to_string(), "0 \"a b c\" "); //shown joined with spaces
+        //by default only a space separates words
+        assert_eq!(added("add \"a b c\"", &ParserConfig::default()), abc);
+        assert!(parse("add \"a,b,c\"", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn offset_and_limit() {
+        let sp = search_params("search water :offset 6 :limit 3");
+        assert_eq!((sp.offset, sp.limit), (6, Some(3)));
+        let sp = search_params("search :limit 3 water :offset 6");
+        assert_eq!((sp.offset, sp.limit), (6, Some(3)));
+        assert_eq!((search_params("search water").offset, search_params("search water").limit), (0, None));
+        assert!(parse("search water :limit", &ParserConfig::default()).is_err());
+    }
+
+    #[test]
+    fn next_index_is_whole_line() {
+        assert_eq!(parsed("next-index"), Query::NextIndex);
+        assert_eq!(parsed("next-index "), Query::NextIndex);
+        assert!(parse("next-index 5", &ParserConfig::default()).is_err());
+        assert!(parse("next-indexes", &ParserConfig::default()).is_err());
+    }
+}
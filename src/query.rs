@@ -1,24 +1,275 @@
 use std::fmt;
 
+use serde::Serialize;
+
 use crate::*;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Query {
-    Add (Vec<Word>, Vec<Tag>),
-    Done (Index),
+    Add (Vec<Word>, Vec<Tag>, AddOptions),
+    Done (Index, Option<String>), //with a note on how the item was resolved
+    Toggle (Index),
+    SetDone (Index, bool),
+    Retag (Index, Tag, Tag),
+    ClearTags (Index),
+    Edit (Index, Vec<Word>, Option<Option<String>>), //with the item's new note if one was given (None inside to remove it)
+    Bump (Index, i32),
+    Swap (Index, Index),
+    Show (Vec<Index>),
+    Reset,
     Search (SearchParams),
+    SearchNote (String),
+    DoneList,
+    NextIndex,
+}
+
+impl Query {
+    //the command the query starts with, for grouping queries by kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            Query::Add(..) => "add",
+            Query::Done(..) => "done",
+            Query::Toggle(_) => "toggle",
+            Query::SetDone(..) => "set-done",
+            Query::Retag(..) => "retag",
+            Query::ClearTags(_) => "clear-tags",
+            Query::Edit(..) => "edit",
+            Query::Bump(..) => "bump",
+            Query::Swap(..) => "swap",
+            Query::Show(_) => "show",
+            Query::Reset => "reset",
+            Query::Search(_) => "search",
+            Query::SearchNote(_) => "search-note",
+            Query::DoneList => "done-list",
+            Query::NextIndex => "next-index",
+        }
+    }
+    //whether running the query can change the list (searches and the like only read it)
+    pub fn mutates(&self) -> bool {
+        !matches!(self, Query::Search(_) | Query::SearchNote(_) | Query::Show(_) | Query::DoneList | Query::NextIndex)
+    }
+}
+
+//the optional modifiers which can follow an add query's tags
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AddOptions {
+    pub priority : Option<u8>,
+    pub due : Option<Date>,
+    pub note : Option<String>,
+}
+
+//how a search term is compared with each word (or tag)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    Subsequence, //the term's characters appear in the word in order, possibly with gaps between them
+    MaxGap (usize), //like Subsequence, but skipping at most this many characters between matched ones (0 is a substring match)
+    Prefix,
+    Exact,
+    Glob, //the whole word matches the term, where * in the term stands for any run of characters and ? for any single one
+}
+
+//whether an item has to match every search term, or just one of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CombineMode {
+    All,
+    Any,
+}
+
+//how search tags are compared with item tags, unlike words a tag has to match exactly unless LikeWords is chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagPolicy {
+    #[default]
+    Exact,
+    LikeWords, //the search's match mode, subsequence unless a modifier changes it
+}
+
+//the order search results are given in, offset and limit apply after ordering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResultOrder {
+    #[default]
+    Index, //ascending index order
+    Length, //fewest description words first, equally long ones in index order
+    LengthDescending, //most description words first, equally long ones in index order
+}
+
+//an empty group of terms doesn't rule anything out
+fn combine_terms<'a>(combine: CombineMode, mut terms: impl Iterator<Item = &'a todo_list::SearchWordOrTag>, matches: &mut impl FnMut(&todo_list::SearchWordOrTag) -> bool) -> bool {
+    match terms.next() {
+        None => true,
+        Some(first) => match combine {
+            CombineMode::All => matches(first) && terms.all(matches),
+            CombineMode::Any => matches(first) || terms.any(matches),
+        },
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SearchParams {
     pub params : Vec<todo_list::SearchWordOrTag>,
+    pub mode : MatchMode,
+    pub combine : CombineMode,
+    //when set, tag terms are combined this way and word terms by combine, and an item has to match both groups
+    pub tag_combine : Option<CombineMode>,
+    pub offset : usize, //number of results (in ascending index order) to skip
+    pub limit : Option<usize>, //maximum number of results to return after skipping
+    pub words_ignore_case : bool, //whether description words are compared case-insensitively
+    pub tags_ignore_case : bool, //and tags, separately
+    pub tag_policy : TagPolicy,
+    pub untagged : bool, //only items without any tags, in addition to matching the terms (whatever the combine mode)
+    pub include_done : bool, //done items can match too, rather than being skipped
+    pub negate : bool, //the items which don't match the terms (as combined), before the other filters and pagination apply
+    pub excluded : Vec<todo_list::SearchWordOrTag>, //items matching any of these are left out, like untagged this is on top of the terms
+    pub order : ResultOrder,
+}
+impl SearchParams {
+    pub fn new(params: Vec<todo_list::SearchWordOrTag>) -> Self {
+        SearchParams {
+            params,
+            mode: MatchMode::Subsequence,
+            combine: CombineMode::All,
+            tag_combine: None,
+            offset: 0,
+            limit: None,
+            words_ignore_case: false,
+            tags_ignore_case: false,
+            tag_policy: TagPolicy::Exact,
+            untagged: false,
+            include_done: false,
+            negate: false,
+            excluded: Vec::new(),
+            order: ResultOrder::Index,
+        }
+    }
+    //for building searches without going through the parser, rejects empty words which the parser couldn't produce
+    //(an empty tag is allowed, it's what a bare # parses to)
+    pub fn from_params(params: Vec<todo_list::SearchWordOrTag>) -> Result<Self, QueryError> {
+        for param in &params {
+            match param.split_mode().0 {
+                SearchWordOrTag::RawWord(w) if w.is_empty() => return Err(QueryError(String::from("Search words can't be empty"))),
+                SearchWordOrTag::RawAny(s) if s.is_empty() => return Err(QueryError(String::from("Search terms can't be empty"))),
+                _ => (),
+            }
+        }
+        Ok(Self::new(params))
+    }
+    pub fn with_mode(mut self, mode: MatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn with_max_gap(mut self, max_gap: usize) -> Self {
+        self.mode = MatchMode::MaxGap(max_gap);
+        self
+    }
+    pub fn with_combine(mut self, combine: CombineMode) -> Self {
+        self.combine = combine;
+        self
+    }
+    pub fn with_tag_combine(mut self, tag_combine: CombineMode) -> Self {
+        self.tag_combine = Some(tag_combine);
+        self
+    }
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+    pub fn with_words_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.words_ignore_case = ignore_case;
+        self
+    }
+    pub fn with_tags_ignore_case(mut self, ignore_case: bool) -> Self {
+        self.tags_ignore_case = ignore_case;
+        self
+    }
+    pub fn with_tag_policy(mut self, tag_policy: TagPolicy) -> Self {
+        self.tag_policy = tag_policy;
+        self
+    }
+    pub fn with_untagged(mut self, untagged: bool) -> Self {
+        self.untagged = untagged;
+        self
+    }
+    pub fn with_include_done(mut self, include_done: bool) -> Self {
+        self.include_done = include_done;
+        self
+    }
+    pub fn with_negate(mut self, negate: bool) -> Self {
+        self.negate = negate;
+        self
+    }
+    pub fn with_excluded(mut self, excluded: Vec<todo_list::SearchWordOrTag>) -> Self {
+        self.excluded = excluded;
+        self
+    }
+    pub fn with_order(mut self, order: ResultOrder) -> Self {
+        self.order = order;
+        self
+    }
+    //whether an item passes the filters which apply on top of the terms (including skipping done items)
+    pub fn keeps(&self, item: &todo_list::TodoItem) -> bool {
+        (self.include_done || !item.done) && (!self.untagged || item.tags.is_empty())
+            && !self.excluded.iter().any(|param| todo_list::TodoList::match_param(item, param, self))
+    }
+    //the match mode a tag term (or the tag side of an any-field term) is compared with
+    //a term ending in / like #work/ is a path, matching every tag under it like #work/project whatever the policy
+    pub fn tag_mode(&self, search_tag: &str) -> MatchMode {
+        self.term_tag_mode(search_tag, None)
+    }
+    //like tag_mode, but a term's own mode (see SearchWordOrTag::WithMode) is used whatever the policy, paths are still prefixes
+    pub fn term_tag_mode(&self, search_tag: &str, own_mode: Option<MatchMode>) -> MatchMode {
+        match (self.tag_policy, own_mode) {
+            _ if search_tag.ends_with('/') => MatchMode::Prefix,
+            (_, Some(mode)) => mode,
+            (TagPolicy::Exact, None) => MatchMode::Exact,
+            (TagPolicy::LikeWords, None) => self.mode,
+        }
+    }
+    //whether a term goes with the tags rather than the words when they're combined separately (see tag_combine)
+    pub fn is_tag_term(param: &todo_list::SearchWordOrTag) -> bool {
+        matches!(param.split_mode().0, SearchWordOrTag::RawTag(_))
+    }
+    //whether an item matches the terms as combined, given whether it matches each one, no terms matches every item
+    pub fn combines(&self, mut matches: impl FnMut(&todo_list::SearchWordOrTag) -> bool) -> bool {
+        match self.tag_combine {
+            None => combine_terms(self.combine, self.params.iter(), &mut matches),
+            Some(tag_combine) => combine_terms(self.combine, self.params.iter().filter(|p| !Self::is_tag_term(p)), &mut matches)
+                && combine_terms(tag_combine, self.params.iter().filter(|p| Self::is_tag_term(p)), &mut matches),
+        }
+    }
+    //puts results which are already in ascending index order into the search's order, then applies offset and limit
+    pub fn paginate<'a>(&self, results: impl IntoIterator<Item = &'a TodoItem>) -> Vec<&'a TodoItem> {
+        let limit = self.limit.unwrap_or(usize::MAX);
+        if self.order == ResultOrder::Index { //nothing to sort, so the results can be skipped and taken lazily
+            return results.into_iter().skip(self.offset).take(limit).collect()
+        }
+        let mut results: Vec<&TodoItem> = results.into_iter().collect();
+        match self.order {
+            ResultOrder::Index => (),
+            ResultOrder::Length => results.sort_by_key(|item| item.description.len()), //stable, so ties stay in index order
+            ResultOrder::LengthDescending => results.sort_by_key(|item| std::cmp::Reverse(item.description.len())),
+        }
+        results.into_iter().skip(self.offset).take(limit).collect()
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum QueryResult {
     Added (TodoItem),
     Done,
-    Found (Vec<todo_list::TodoItem>),
+    Toggled (bool),
+    Retagged,
+    TagsCleared (usize), //how many tags the item had
+    Edited (Vec<Word>), //the description before editing
+    Priority (u8),
+    Swapped,
+    Reset,
+    Found (Vec<todo_list::TodoItem>), //never empty, see NoMatch
+    NoMatch, //a query which finds items ran fine but found none (a query which couldn't be run gives no result at all)
+    Shown (Vec<(Index, Option<TodoItem>)>), //in the order asked for, None where there's no item or it's done
+    NextIndex (Index),
 }
 
 impl fmt::Display for QueryResult {
@@ -26,6 +277,13 @@ impl fmt::Display for QueryResult {
         match &self {
             QueryResult::Added(ti) => write!(f, "{}", ti.index),
             QueryResult::Done => write!(f, "done"),
+            QueryResult::Toggled(done) => write!(f, "{}", if *done { "done" } else { "undone" }),
+            QueryResult::Retagged => write!(f, "retagged"),
+            QueryResult::TagsCleared(n) => write!(f, "{} tag(s) removed", n),
+            QueryResult::Edited(old) => write!(f, "edited, was \"{}\"", old.iter().map(|w| w.value()).collect::<Vec<_>>().join(" ")),
+            QueryResult::Priority(p) => write!(f, "priority {}", p),
+            QueryResult::Swapped => write!(f, "swapped"),
+            QueryResult::Reset => write!(f, "reset"),
             QueryResult::Found(rs) => {
                 let mut buff : Vec<String> = Vec::new();
                 buff.push(format!("{} item(s) found", rs.len()));
@@ -34,6 +292,46 @@ impl fmt::Display for QueryResult {
                 }
                 write!(f, "{}", buff.join("\n"))
             }
+            QueryResult::NoMatch => write!(f, "0 item(s) found"),
+            QueryResult::Shown(rs) => {
+                let buff : Vec<String> = rs.iter().map(|(idx, item)| match item {
+                    Some(i) => format!("{}", i),
+                    None => format!("{} not found", idx),
+                }).collect();
+                write!(f, "{}", buff.join("\n"))
+            }
+            QueryResult::NextIndex(idx) => write!(f, "{}", idx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    JsonLines, //one JSON value per line, search results produce one line per item found (and no count line), or "NoMatch" if there are none
+}
+
+impl QueryResult {
+    //the result of a query which finds items
+    pub fn found(items: Vec<todo_list::TodoItem>) -> Self {
+        if items.is_empty() {
+            QueryResult::NoMatch
+        } else {
+            QueryResult::Found(items)
+        }
+    }
+    //the lines to output for this result, Text lines are the same as the Display implementation
+    pub fn to_lines(&self, format: OutputFormat) -> Vec<String> {
+        match format {
+            OutputFormat::Text => vec![self.to_string()],
+            OutputFormat::JsonLines => match &self {
+                QueryResult::Found(rs) => rs.iter().map(|i| serde_json::to_string(i).unwrap()).collect(),
+                QueryResult::Shown(rs) => rs.iter().map(|(idx, item)| match item {
+                    Some(i) => serde_json::to_string(i).unwrap(),
+                    None => format!("{{\"Missing\":{}}}", idx),
+                }).collect(),
+                _ => vec![serde_json::to_string(self).unwrap()],
+            }
         }
     }
 }
@@ -46,3 +344,57 @@ impl fmt::Display for QueryError {
         write!(f, "An error occurred while processing the query: {}.", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lines_parse_separately() {
+        let items = vec![
+            TodoItem::new(Index::new(0), vec![Word::new("water").unwrap()], Tag::from_strings(["home"]), false),
+            TodoItem::new(Index::new(4), vec![Word::new("wash").unwrap(), Word::new("car").unwrap()], Vec::new(), false),
+        ];
+        let lines = QueryResult::found(items.clone()).to_lines(OutputFormat::JsonLines);
+        assert_eq!(lines.len(), 2);
+        for (line, item) in lines.iter().zip(&items) {
+            assert_eq!(&serde_json::from_str::<TodoItem>(line).unwrap(), item);
+        }
+        //an empty result still gives a line, so a reader can tell the query ran
+        let lines = QueryResult::found(Vec::new()).to_lines(OutputFormat::JsonLines);
+        assert_eq!(lines, vec![String::from("\"NoMatch\"")]);
+        assert_eq!(serde_json::from_str::<String>(&lines[0]).unwrap(), "NoMatch");
+    }
+
+    #[test]
+    fn ordered_by_length() {
+        let item = |i: u64, description: &str| TodoItem::new(Index::new(i), description.split(' ').map(|w| Word::new(w).unwrap()).collect(), Vec::new(), false);
+        let items = vec![item(0, "water the plants"), item(1, "shop"), item(2, "walk the dog today"), item(3, "wash car"), item(4, "pay rent")];
+        let order = |sp: SearchParams| sp.paginate(&items).into_iter().map(|item| item.index.value()).collect::<Vec<u64>>();
+        let sp = || SearchParams::new(Vec::new());
+        assert_eq!(order(sp()), vec![0, 1, 2, 3, 4]);
+        assert_eq!(order(sp().with_order(ResultOrder::Length)), vec![1, 3, 4, 0, 2]);
+        assert_eq!(order(sp().with_order(ResultOrder::LengthDescending)), vec![2, 0, 3, 4, 1]);
+        //offset and limit come after ordering
+        assert_eq!(order(sp().with_order(ResultOrder::Length).with_offset(1).with_limit(2)), vec![3, 4]);
+    }
+
+    #[test]
+    fn checked_search_params() {
+        let word = |w: &str| SearchWordOrTag::RawWord(w.to_string());
+        let mut tl = TodoList::new();
+        tl.push(vec![Word::new("water").unwrap(), Word::new("plants").unwrap()], Tag::from_strings(["home"]));
+        tl.push(vec![Word::new("wash").unwrap(), Word::new("car").unwrap()], Vec::new());
+        tl.push(vec![Word::new("walk").unwrap(), Word::new("dog").unwrap()], Tag::from_strings(["home"]));
+        let sp = SearchParams::from_params(vec![word("wa"), SearchWordOrTag::RawTag(String::from("home"))]).unwrap()
+            .with_mode(MatchMode::Prefix)
+            .with_limit(1);
+        assert_eq!(tl.search(sp.clone()).into_iter().map(|item| item.index.value()).collect::<Vec<u64>>(), vec![0]);
+        assert_eq!(tl.search(sp.with_offset(1)).into_iter().map(|item| item.index.value()).collect::<Vec<u64>>(), vec![2]);
+        //a bare tag is fine, an empty word (even with its own mode) isn't
+        assert!(SearchParams::from_params(vec![SearchWordOrTag::RawTag(String::new())]).is_ok());
+        assert_eq!(SearchParams::from_params(vec![word("wa"), word("")]), Err(QueryError(String::from("Search words can't be empty"))));
+        assert!(SearchParams::from_params(vec![SearchWordOrTag::WithMode(MatchMode::Exact, Box::new(word("")))]).is_err());
+        assert!(SearchParams::from_params(vec![SearchWordOrTag::RawAny(String::new())]).is_err());
+    }
+}
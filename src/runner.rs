@@ -1,14 +1,241 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+
 use crate::*;
 
+//what to do when a done query refers to an index with no item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingIndexPolicy {
+    #[default]
+    Error, //report it on stderr like any other failed query
+    Ignore, //treat it as done already, with no error and no output
+}
+
+//Options changing how queries are run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunnerConfig {
+    pub parser: parser::ParserConfig,
+    pub missing_done: MissingIndexPolicy,
+    //the most lines run_reader writes for a single query's result, the rest are replaced by a notice (None for no cap)
+    //unlike a search's limit this is a safety net, so a very broad search can't produce an enormous output
+    pub max_result_lines: Option<usize>,
+}
+
+impl Default for RunnerConfig {
+    fn default() -> Self {
+        RunnerConfig {
+            parser: parser::ParserConfig::default(),
+            missing_done: MissingIndexPolicy::default(),
+            max_result_lines: Some(10_000),
+        }
+    }
+}
+
 pub fn run_line<T: TodoLister>(line: &str, tl: &mut T) -> Option<QueryResult> {
-    match parser::query(line) {
-        Ok((_, q)) => match run_query(q, tl) {
-            Ok(r) => Some(r),
-            Err(e) => { 
-                eprintln!("Error: {}", e);
-                None
-            },
+    run_line_with(line, tl, &parser::ParserConfig::default())
+}
+
+pub fn run_line_with<T: TodoLister>(line: &str, tl: &mut T, config: &parser::ParserConfig) -> Option<QueryResult> {
+    run_line_config(line, tl, &RunnerConfig { parser: config.clone(), ..RunnerConfig::default() })
+}
+
+pub fn run_line_config<T: TodoLister>(line: &str, tl: &mut T, config: &RunnerConfig) -> Option<QueryResult> {
+    parse_line(line, &config.parser).and_then(|q| run_parsed(q, tl, config))
+}
+
+//the number of queries of each kind run by run_line_timed, and the wall-clock time they took altogether (parsing included)
+#[cfg(feature = "timing")]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RunStats {
+    totals: std::collections::BTreeMap<&'static str, (usize, std::time::Duration)>, //keyed by Query::name
+}
+#[cfg(feature = "timing")]
+impl RunStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn count(&self, name: &str) -> usize {
+        self.totals.get(name).map_or(0, |(count, _)| *count)
+    }
+    pub fn total(&self, name: &str) -> std::time::Duration {
+        self.totals.get(name).map_or(std::time::Duration::ZERO, |(_, total)| *total)
+    }
+    //every kind of query run so far, in alphabetical order
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, usize, std::time::Duration)> + '_ {
+        self.totals.iter().map(|(name, (count, total))| (*name, *count, *total))
+    }
+}
+
+//like run_line_config, adding the time taken to the stats for the query's kind (a line which doesn't parse isn't counted)
+//queries which parse but fail to run are still counted, since the time was spent on them
+#[cfg(feature = "timing")]
+pub fn run_line_timed<T: TodoLister>(line: &str, tl: &mut T, config: &RunnerConfig, stats: &mut RunStats) -> Option<QueryResult> {
+    let start = std::time::Instant::now();
+    let q = parse_line(line, &config.parser)?;
+    let name = q.name();
+    let r = run_parsed(q, tl, config);
+    let (count, total) = stats.totals.entry(name).or_default();
+    *count += 1;
+    *total += start.elapsed();
+    r
+}
+
+//runs each query after the first line of input (the query count), writing the results to the output
+//when a recorder is given, every query which parsed is added to it in order, so the session can be replayed later
+//flush_each writes each result out before reading the next line (for interactive use), instead of leaving it to the writer
+pub fn run_reader<T: TodoLister, R: BufRead, W: Write>(input: R, output: &mut W, tl: &mut T, config: &RunnerConfig, format: OutputFormat, flush_each: bool, recorder: Option<&mut Vec<Query>>) -> io::Result<()> {
+    let mut lines_in = input.lines();
+    if let Some(Ok(_s)) = lines_in.next() {
+        run_lines(readable_lines(lines_in), output, tl, config, format, flush_each, recorder)?;
+    }
+    Ok(())
+}
+
+//the input to give run_reader, decompressed if it's gzipped (told by its first two bytes rather than a file name)
+#[cfg(feature = "gzip")]
+pub fn input_reader<'a, R: BufRead + 'a>(mut input: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    if input.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        return Ok(Box::new(io::BufReader::new(flate2::read::GzDecoder::new(input))))
+    }
+    Ok(Box::new(input))
+}
+//without the gzip feature the input is passed on unchanged
+#[cfg(not(feature = "gzip"))]
+pub fn input_reader<'a, R: BufRead + 'a>(input: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(Box::new(input))
+}
+
+//skips lines which aren't valid UTF-8, so one bad line doesn't end the input
+//any other read error does end it, since reading again may just fail again
+pub fn readable_lines(lines: impl Iterator<Item = io::Result<String>>) -> impl Iterator<Item = String> {
+    lines.take_while(|line| !matches!(line, Err(e) if e.kind() != io::ErrorKind::InvalidData))
+        .filter_map(Result::ok)
+}
+
+//like run_reader, but every line is a query (there's no count line to skip), so input can be split across several sources
+pub fn run_lines<T: TodoLister, I: IntoIterator<Item = String>, W: Write>(lines: I, output: &mut W, tl: &mut T, config: &RunnerConfig, format: OutputFormat, flush_each: bool, mut recorder: Option<&mut Vec<Query>>) -> io::Result<()> {
+    for line in lines {
+        if let Some(q) = parse_line(&line, &config.parser) {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.push(q.clone());
+            }
+            if let Some(r) = run_parsed(q, tl, config) {
+                write_capped(output, &r, format, config.max_result_lines)?;
+                if flush_each {
+                    output.flush()?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+//a result's lines up to the cap, and then a notice of how many were left out (as JSON for JsonLines)
+fn write_capped<W: Write>(output: &mut W, r: &QueryResult, format: OutputFormat, cap: Option<usize>) -> io::Result<()> {
+    let results = r.to_lines(format);
+    let mut lines = results.iter().flat_map(|out| out.lines()); //a Text result is a single string of several lines
+    for line in lines.by_ref().take(cap.unwrap_or(usize::MAX)) {
+        writeln!(output, "{}", line)?;
+    }
+    let truncated = lines.count();
+    if truncated > 0 {
+        match format {
+            OutputFormat::Text => writeln!(output, "... {} more line(s) truncated", truncated)?,
+            OutputFormat::JsonLines => writeln!(output, "{{\"Truncated\":{}}}", truncated)?,
+        }
+    }
+    Ok(())
+}
+
+//writes the items a search finds, one per line (Text lines are each item's Display, without a count line)
+//returns how many were written, JsonLines output can be read back with read_items
+pub fn export<T: TodoLister, W: Write>(tl: &T, sp: SearchParams, output: &mut W, format: OutputFormat) -> io::Result<usize> {
+    let items = tl.search(sp);
+    for item in &items {
+        match format {
+            OutputFormat::Text => writeln!(output, "{}", item)?,
+            OutputFormat::JsonLines => writeln!(output, "{}", serde_json::to_string(item).unwrap())?, //plain data always serializes
         }
+    }
+    Ok(items.len())
+}
+
+pub fn export_to_file<T: TodoLister>(tl: &T, sp: SearchParams, path: &str, format: OutputFormat) -> io::Result<usize> {
+    let mut buffer_out = io::BufWriter::new(fs::File::create(path)?);
+    let count = export(tl, sp, &mut buffer_out, format)?;
+    buffer_out.flush()?;
+    Ok(count)
+}
+
+//reads items written by export as JSON lines, for loading into a list with from_items
+pub fn read_items<R: BufRead>(input: R) -> Result<Vec<TodoItem>, QueryError> {
+    let mut items = Vec::new();
+    for line in input.lines() {
+        let line = line.map_err(|e| QueryError(format!("Could not read items: {}", e)))?;
+        if line.is_empty() {
+            continue
+        }
+        items.push(serde_json::from_str(&line).map_err(|e| QueryError(format!("Could not read item \"{}\": {}", line, e)))?);
+    }
+    Ok(items)
+}
+
+//runs every line of the script as a query (there's no count line to skip, unlike run_reader)
+//giving each one's output as text, or None where it failed to parse or run
+pub fn run_script<T: TodoLister>(script: &str, tl: &mut T) -> Vec<Option<String>> {
+    script.lines().map(|line| run_line(line, tl).map(|r| r.to_string())).collect()
+}
+
+//runs previously recorded queries in order, returning the results of those which succeeded
+pub fn replay<T: TodoLister>(queries: &[Query], tl: &mut T) -> Vec<QueryResult> {
+    let config = RunnerConfig::default();
+    queries.iter().cloned().filter_map(|q| run_parsed(q, tl, &config)).collect()
+}
+
+//an append-only log of the queries which change a list, one query line each, so the list can be rebuilt with replay_log
+//each line is written before its query runs (a query which then fails will fail the same way when replayed)
+#[derive(Debug)]
+pub struct OpLog {
+    file: fs::File,
+}
+impl OpLog {
+    //creates the log if it isn't there, otherwise new queries are added after those already in it
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(OpLog { file })
+    }
+    //runs a line like run_line_config, logging it first if it parsed to a query which changes the list
+    pub fn run_line<T: TodoLister>(&mut self, line: &str, tl: &mut T, config: &RunnerConfig) -> io::Result<Option<QueryResult>> {
+        let Some(q) = parse_line(line, &config.parser) else {
+            return Ok(None)
+        };
+        if q.mutates() {
+            self.file.write_all(format!("{}\n", line.trim_end()).as_bytes())?; //a single write, so a line is never split
+        }
+        Ok(run_parsed(q, tl, config))
+    }
+}
+
+//runs every query in a log written by OpLog, returning how many of them succeeded
+//the lines are parsed with the default parser config, use replay_log_config if they were logged with another one
+pub fn replay_log<T: TodoLister>(path: &str, tl: &mut T) -> io::Result<usize> {
+    replay_log_config(path, tl, &RunnerConfig::default())
+}
+
+pub fn replay_log_config<T: TodoLister>(path: &str, tl: &mut T, config: &RunnerConfig) -> io::Result<usize> {
+    let file_in = io::BufReader::new(fs::File::open(path)?);
+    let mut count = 0;
+    for line in file_in.lines() {
+        if run_line_config(&line?, tl, config).is_some() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+fn parse_line(line: &str, config: &parser::ParserConfig) -> Option<Query> {
+    match parser::parse(line, config) {
+        Ok(q) => Some(q),
         Err(e) => {
             eprintln!("Error: {}", e); 
             eprintln!("Attempted to parse: \"{}\"", line);
@@ -17,22 +244,635 @@ pub fn run_line<T: TodoLister>(line: &str, tl: &mut T) -> Option<QueryResult> {
     }
 }
 
+fn run_parsed<T: TodoLister>(q: Query, tl: &mut T, config: &RunnerConfig) -> Option<QueryResult> {
+    match run_with_policies(q, tl, config) {
+        Ok(r) => r,
+        Err(e) => { 
+            eprintln!("Error: {}", e);
+            None
+        },
+    }
+}
+
+//runs the query, dropping the errors the config says to ignore
+fn run_with_policies<T: TodoLister>(q: Query, tl: &mut T, config: &RunnerConfig) -> Result<Option<QueryResult>, QueryError> {
+    let ignore_error = matches!(q, Query::Done(..)) && config.missing_done == MissingIndexPolicy::Ignore; //done only fails for a missing index
+    match run_query(q, tl) {
+        Ok(r) => Ok(Some(r)),
+        Err(_) if ignore_error => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 fn run_query<T: TodoLister>(q: Query, tl: &mut T) -> Result<QueryResult, QueryError> {
     match q {
-        Query::Add(desc, tags) => {
-            let item = tl.push(desc, tags);
+        Query::Add(desc, tags, options) => {
+            let mut item = tl.push(desc, tags);
+            if let Some(p) = options.priority {
+                item.priority = tl.bump_priority(item.index, p as i32).unwrap_or(item.priority);
+            }
+            if options.due.is_some() {
+                tl.set_due(item.index, options.due);
+                item.due = options.due;
+            }
+            if options.note.is_some() {
+                tl.set_note(item.index, options.note.clone());
+                item.note = options.note;
+            }
             Ok(query::QueryResult::Added(item))
         },
-        Query::Done(idx) => {
+        Query::Done(idx, note) => {
             match tl.done_with_index(idx) {
-                Some(_) => Ok(query::QueryResult::Done),
+                Some(_) => {
+                    if note.is_some() {
+                        tl.set_done_note(idx, note);
+                    }
+                    Ok(query::QueryResult::Done)
+                },
                 None => Err(QueryError(String::from("Attempted to mark non-existent item as Done"))),
             }
         },
+        Query::Toggle(idx) => {
+            match tl.toggle_with_index(idx) {
+                Some(done) => Ok(query::QueryResult::Toggled(done)),
+                None => Err(QueryError(String::from("Attempted to toggle non-existent item"))),
+            }
+        },
+        Query::SetDone(idx, done) => {
+            match tl.get(idx) {
+                None => Err(QueryError(String::from("Attempted to set done state of non-existent item"))),
+                Some(item) => {
+                    if item.done != done {
+                        tl.toggle_with_index(idx);
+                    }
+                    Ok(query::QueryResult::Toggled(done))
+                },
+            }
+        },
+        Query::Retag(idx, old, new) => {
+            match tl.retag_with_index(idx, old, new) {
+                Some(_) => Ok(query::QueryResult::Retagged),
+                None => Err(QueryError(String::from("Attempted to retag non-existent item or tag"))),
+            }
+        },
+        Query::ClearTags(idx) => {
+            match tl.clear_tags(idx) {
+                Some(n) => Ok(query::QueryResult::TagsCleared(n)),
+                None => Err(QueryError(String::from("Attempted to clear tags of non-existent item"))),
+            }
+        },
+        Query::Edit(idx, description, note) => {
+            match tl.edit(idx, description) {
+                Some(old) => {
+                    if let Some(note) = note {
+                        tl.set_note(idx, note);
+                    }
+                    Ok(query::QueryResult::Edited(old))
+                },
+                None => Err(QueryError(String::from("Attempted to edit non-existent item"))),
+            }
+        },
+        Query::Bump(idx, delta) => {
+            match tl.bump_priority(idx, delta) {
+                Some(p) => Ok(query::QueryResult::Priority(p)),
+                None => Err(QueryError(String::from("Attempted to bump non-existent item"))),
+            }
+        },
+        Query::Swap(a, b) => {
+            if tl.swap(a, b) {
+                Ok(query::QueryResult::Swapped)
+            } else {
+                Err(QueryError(String::from("Attempted to swap non-existent item")))
+            }
+        },
+        Query::Show(idxs) => {
+            let items = tl.get_many(&idxs).into_iter().map(|item| item.cloned());
+            Ok(query::QueryResult::Shown(idxs.into_iter().zip(items).collect()))
+        },
+        Query::Reset => {
+            tl.reset();
+            Ok(query::QueryResult::Reset)
+        },
         Query::Search(params) => {
-            let results = tl.search(params);
-            let results = results.into_iter().map(|r| r.clone()).collect();
-            Ok(query::QueryResult::Found(results))
+            Ok(query::QueryResult::found(tl.search_owned(params)))
+        },
+        Query::SearchNote(text) => {
+            let results = tl.search_note(&text).into_iter().cloned().collect();
+            Ok(query::QueryResult::found(results))
         },
+        Query::DoneList => {
+            let results = tl.done_items().into_iter().cloned().collect();
+            Ok(query::QueryResult::found(results))
+        },
+        Query::NextIndex => Ok(query::QueryResult::NextIndex(tl.next_index())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    //runs each line in turn, giving each one's text output
+    fn run_all<T: TodoLister>(lines: &[&str], tl: &mut T) -> Vec<Option<String>> {
+        lines.iter().map(|line| run_line(line, tl).map(|r| r.to_string())).collect()
+    }
+
+    #[test]
+    fn notes() {
+        let mut tl = TodoList::new();
+        let out = run_all(&[
+            "add \"buy milk\" #shop note \"semi skimmed\"",
+            "search skimmed",
+            "search-note skim",
+            "edit 0 \"buy milk\" note \"\"",
+            "search-note skim",
+        ], &mut tl);
+        assert_eq!(out[1].as_deref(), Some("0 item(s) found")); //only search-note looks at notes
+        assert_eq!(out[2].as_deref(), Some("1 item(s) found\n0 \"buy milk\" #shop note \"semi skimmed\""));
+        assert_eq!(out[4].as_deref(), Some("0 item(s) found"));
+        assert_eq!(tl.get(Index::new(0)).unwrap().note, None);
+    }
+
+    #[test]
+    fn json_lines_empty_search() {
+        let mut tl = TodoList::new();
+        let mut output = Vec::new();
+        let lines = ["add \"buy milk\"", "search bread", "search milk"].iter().map(|l| l.to_string());
+        run_lines(lines, &mut output, &mut tl, &RunnerConfig::default(), OutputFormat::JsonLines, false, None).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], "\"NoMatch\"");
+        for line in lines {
+            assert!(serde_json::from_str::<serde_json::Value>(line).is_ok(), "{} isn't JSON", line);
+        }
+    }
+
+    #[test]
+    fn reset_restarts_list() {
+        fn check<T: TodoLister + PartialEq + std::fmt::Debug>(mut tl: T, fresh: T) {
+            run_all(&["add \"buy milk\"", "add \"buy bread\"", "done 0", "reset"], &mut tl);
+            assert_eq!(tl, fresh); //nothing is left over, not even the completion count or the last done item
+            let out = run_all(&["search buy", "add \"walk dog\""], &mut tl);
+            assert_eq!(out[0].as_deref(), Some("0 item(s) found"));
+            assert_eq!(out[1].as_deref(), Some("0"));
+            assert_eq!(tl.len(), 1);
+        }
+        check(TodoList::new(), TodoList::new());
+        check(TodoList2::new(), TodoList2::new());
+        check(TriedoList::<Trie4>::new(), TriedoList::<Trie4>::new());
+        check(TriedoList::<RadixTrie>::new(), TriedoList::<RadixTrie>::new());
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let input = "5\nadd \"buy milk\" #shop\nadd \"walk dog\"\ndone 0\nbogus\nsearch walk\n";
+        let mut tl = TriedoList::<Trie4>::new();
+        let mut recorded = Vec::new();
+        run_reader(input.as_bytes(), &mut Vec::new(), &mut tl, &RunnerConfig::default(), OutputFormat::Text, false, Some(&mut recorded)).unwrap();
+        assert_eq!(recorded.len(), 4); //the line which didn't parse isn't recorded
+        let mut replayed = TriedoList::<Trie4>::new();
+        assert_eq!(replay(&recorded, &mut replayed).len(), 4);
+        assert_eq!(replayed, tl);
+    }
+
+    #[test]
+    fn invalid_utf8_line_is_skipped() {
+        let mut input = b"3\nadd \"buy milk\"\n".to_vec();
+        input.extend_from_slice(b"add \"\xff\"\n");
+        input.extend_from_slice(b"search milk\n");
+        let mut output = Vec::new();
+        run_reader(&input[..], &mut output, &mut TodoList::new(), &RunnerConfig::default(), OutputFormat::Text, false, None).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "0\n1 item(s) found\n0 \"buy milk\" \n");
+    }
+
+    //gives one line per read, noting how much output had been written by then
+    struct SlowInput {
+        lines: Vec<&'static str>,
+        output: Rc<RefCell<Vec<u8>>>,
+        written: Vec<usize>, //output length at each read
+    }
+    impl io::Read for SlowInput {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.written.push(self.output.borrow().len());
+            match self.lines.get(self.written.len() - 1) {
+                Some(line) => {
+                    buf[..line.len()].copy_from_slice(line.as_bytes());
+                    Ok(line.len())
+                }
+                None => Ok(0),
+            }
+        }
+    }
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flush_each_result() {
+        for flush_each in [true, false] {
+            let output = Rc::new(RefCell::new(Vec::new()));
+            let mut input = io::BufReader::new(SlowInput { lines: vec!["2\n", "add \"buy milk\"\n", "search milk\n"], output: output.clone(), written: Vec::new() });
+            let mut writer = io::BufWriter::new(SharedOutput(output.clone()));
+            run_reader(&mut input, &mut writer, &mut TodoList::new(), &RunnerConfig::default(), OutputFormat::Text, flush_each, None).unwrap();
+            //the add's result is out before the search is read only when flushing each one
+            let before_search = input.get_ref().written[2];
+            assert_eq!(before_search > 0, flush_each);
+        }
+    }
+
+    #[test]
+    fn set_done_both_ways() {
+        let mut tl = TodoList::new();
+        let out = run_all(&["add \"buy milk\"", "set-done 0 true", "set-done 0 true", "search milk", "set-done 0 false", "search milk", "set-done 1 true"], &mut tl);
+        assert_eq!(out[1], out[2]); //setting the same state again leaves it
+        assert_eq!(out[3].as_deref(), Some("0 item(s) found"));
+        assert_eq!(out[5].as_deref(), Some("1 item(s) found\n0 \"buy milk\" "));
+        assert_eq!(out[6], None);
+        assert!(!tl.get(Index::new(0)).unwrap().done);
+    }
+
+    #[test]
+    fn search_quoted_tag() {
+        let mut tl = TriedoList::<Trie4>::new();
+        let out = run_all(&["add \"ship release\" #\"high prio\"", "add \"tidy desk\" #high", "search #\"high prio\"", "search #high"], &mut tl);
+        assert_eq!(out[2].as_deref(), Some("1 item(s) found\n0 \"ship release\" #\"high prio\""));
+        assert_eq!(out[3].as_deref(), Some("1 item(s) found\n1 \"tidy desk\" #high"));
+    }
+
+    #[test]
+    fn search_untagged() {
+        let lines = ["add \"water plants\" #home", "add \"wash car\"", "add \"walk dog\"", "search :untagged", "search :untagged dog", "search :untagged plants"];
+        let expected = [
+            Some("2 item(s) found\n1 \"wash car\" \n2 \"walk dog\" "),
+            Some("1 item(s) found\n2 \"walk dog\" "),
+            Some("0 item(s) found"),
+        ];
+        assert_eq!(run_all(&lines, &mut TodoList::new())[3..], expected.map(|s| s.map(String::from)));
+        assert_eq!(run_all(&lines, &mut TriedoList::<Trie4>::new())[3..], expected.map(|s| s.map(String::from)));
+    }
+
+    #[test]
+    fn next_index_is_the_next_add() {
+        let lines = ["next-index", "add \"buy milk\"", "next-index", "next-index", "add \"buy bread\""];
+        let out = run_all(&lines, &mut TodoList::new());
+        assert_eq!((&out[0], &out[2]), (&out[1], &out[4]));
+        assert_eq!(out[2], out[3]); //asking doesn't use the index up
+        //continuing from loaded items
+        let loaded = vec![TodoItem::new(Index::new(5), vec![Word::new("water").unwrap()], Vec::new(), false)];
+        let out = run_all(&lines[..2], &mut TriedoList::<Trie4>::from_items(loaded).unwrap());
+        assert_eq!(out[0].as_deref(), Some("6"));
+        assert_eq!(out[0], out[1]);
+    }
+
+    #[test]
+    fn missing_done_policies() {
+        let mut tl = TodoList::new();
+        run_line("add \"buy milk\"", &mut tl);
+        let config = |missing_done| RunnerConfig { missing_done, ..RunnerConfig::default() };
+        let done = |tl: &mut TodoList, missing_done| run_with_policies(Query::Done(Index::new(4), None), tl, &config(missing_done));
+        assert!(done(&mut tl, MissingIndexPolicy::Error).is_err());
+        assert_eq!(done(&mut tl, MissingIndexPolicy::Ignore), Ok(None));
+        //other failures are still errors, and an item that's there is done either way
+        assert!(run_with_policies(Query::Toggle(Index::new(4)), &mut tl, &config(MissingIndexPolicy::Ignore)).is_err());
+        assert!(run_with_policies(Query::Done(Index::new(0), None), &mut tl, &config(MissingIndexPolicy::Ignore)).unwrap().is_some());
+        assert_eq!(tl.done_items().len(), 1);
+    }
+
+    #[test]
+    fn no_match_and_invalid_query() {
+        let mut tl = TodoList::new();
+        run_line("add \"buy milk\"", &mut tl);
+        assert_eq!(run_line("search bread", &mut tl), Some(QueryResult::NoMatch));
+        assert_eq!(run_line("search-note bread", &mut tl), Some(QueryResult::NoMatch));
+        assert!(matches!(run_line("search milk", &mut tl), Some(QueryResult::Found(items)) if items.len() == 1));
+        assert_eq!(run_line("search :bogus milk", &mut tl), None); //couldn't be parsed
+        assert_eq!(run_line("done 9", &mut tl), None); //couldn't be run
+    }
+
+    #[test]
+    fn script_outputs_per_line() {
+        let script = "add \"buy milk\" #shop\nadd \"buy bread\"\nsearch buy\nsearch :bogus\ndone 0\nsearch #shop";
+        assert_eq!(run_script(script, &mut TodoList::new()), vec![
+            Some(String::from("0")),
+            Some(String::from("1")),
+            Some(String::from("2 item(s) found\n0 \"buy milk\" #shop\n1 \"buy bread\" ")),
+            None,
+            Some(String::from("done")),
+            Some(String::from("0 item(s) found")),
+        ]);
+    }
+
+    #[test]
+    fn negated_compound_search() {
+        let lines = [
+            "add \"fix login bug\" #work",
+            "add \"fix bike\" #home",
+            "add \"write login docs\" #work",
+            "add \"call mum\"",
+            "search :not #work fix",
+            "search :not fix",
+            "search :not :any #work fix",
+            "done 3",
+            "search :not #work",
+        ];
+        let expected: Vec<Option<String>> = [
+            "3 item(s) found\n1 \"fix bike\" #home\n2 \"write login docs\" #work\n3 \"call mum\" ", //not both
+            "2 item(s) found\n2 \"write login docs\" #work\n3 \"call mum\" ",
+            "1 item(s) found\n3 \"call mum\" ", //neither
+            "done",
+            "1 item(s) found\n1 \"fix bike\" #home", //the complement only has items which aren't done
+        ].iter().map(|s| Some(s.to_string())).collect();
+        assert_eq!(run_all(&lines, &mut TodoList::new())[4..], expected[..]);
+        assert_eq!(run_all(&lines, &mut TriedoList::<RadixTrie>::new())[4..], expected[..]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzipped_input_runs_the_same() {
+        use flate2::{write::GzEncoder, Compression};
+        let input = "4\nadd \"buy milk\" #shop\nadd \"buy bread\"\ndone 1\nsearch buy\n";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(input.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let run = |bytes: &[u8]| {
+            let mut output = Vec::new();
+            let reader = input_reader(bytes).unwrap();
+            run_reader(reader, &mut output, &mut TodoList::new(), &RunnerConfig::default(), OutputFormat::Text, false, None).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(run(&gzipped), run(input.as_bytes()));
+        assert_eq!(run(&gzipped), "0\n1\ndone\n1 item(s) found\n0 \"buy milk\" #shop\n");
+    }
+
+    #[test]
+    fn export_tag_subset_and_reload() {
+        let mut tl = TodoList::new();
+        run_all(&["add \"ship release\" #work", "add \"buy milk\" #home", "add \"book review\" #work #urgent", "done 0"], &mut tl);
+        let path = std::env::temp_dir().join(format!("todo_swamp_export_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap();
+        let sp = SearchParams::new(vec![SearchWordOrTag::RawTag(String::from("work"))]).with_include_done(true);
+        assert_eq!(export_to_file(&tl, sp, path, OutputFormat::JsonLines).unwrap(), 2);
+        let items = read_items(io::BufReader::new(fs::File::open(path).unwrap()));
+        fs::remove_file(path).unwrap();
+        let reloaded = TriedoList::<Trie4>::from_items(items.unwrap()).unwrap();
+        assert_eq!(reloaded.len(), 2);
+        assert!(reloaded.get(Index::new(0)).unwrap().done);
+        assert_eq!(run_script("search #urgent", &mut reloaded.clone()), vec![Some(String::from("1 item(s) found\n2 \"book review\" #work #urgent"))]);
+        assert_eq!(reloaded.next_index(), Index::new(3));
+        //words are checked as they're read
+        let line = |word: &str| serde_json::to_string(&TodoItem::new(Index::new(0), Vec::new(), Vec::new(), false)).unwrap()
+            .replace("\"description\":[]", &format!("\"description\":[\"{}\"]", word));
+        assert!(read_items(line("water").as_bytes()).is_ok());
+        assert!(read_items(line("two words").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn result_lines_are_capped() {
+        let mut lines: Vec<String> = (0..5).map(|n| format!("add \"task{}\"", "x".repeat(n))).collect();
+        lines.push(String::from("search task"));
+        lines.push(String::from("search :limit 2 task"));
+        let run = |format, max_result_lines| {
+            let mut output = Vec::new();
+            let config = RunnerConfig { max_result_lines, ..RunnerConfig::default() };
+            run_lines(lines.clone(), &mut output, &mut TodoList::new(), &config, format, false, None).unwrap();
+            String::from_utf8(output).unwrap().lines().skip(5).map(String::from).collect::<Vec<String>>()
+        };
+        assert_eq!(run(OutputFormat::Text, Some(3)), vec![
+            "5 item(s) found", "0 \"task\" ", "1 \"taskx\" ", "... 3 more line(s) truncated",
+            "2 item(s) found", "0 \"task\" ", "1 \"taskx\" ",
+        ]);
+        assert_eq!(run(OutputFormat::JsonLines, Some(3)).iter().filter(|line| line.starts_with("{\"Truncated\"")).collect::<Vec<_>>(), vec!["{\"Truncated\":2}"]);
+        assert_eq!(run(OutputFormat::Text, None).len(), 6 + 3);
+    }
+
+    #[test]
+    fn replaying_a_log() {
+        let path = std::env::temp_dir().join(format!("todo_swamp_oplog_{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut tl = TodoList::new();
+        let mut log = OpLog::open(path).unwrap();
+        for line in ["add \"buy milk\" #shop", "add \"water plants\"", "search wa", "done 0", "edit 1 \"water lawn\"", "done 7"] {
+            log.run_line(line, &mut tl, &RunnerConfig::default()).unwrap();
+        }
+        drop(log);
+        let logged = fs::read_to_string(path).unwrap();
+        let mut replayed = TodoList::new();
+        let count = replay_log(path, &mut replayed).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(logged.lines().count(), 5); //not the search
+        assert_eq!(count, 4); //done 7 fails again
+        assert_eq!(replayed, tl);
+    }
+
+    #[test]
+    fn done_with_a_note() {
+        let mut tl = TodoList::new();
+        let out = run_all(&[
+            "add \"buy milk\"",
+            "add \"file taxes\" #admin",
+            "add \"walk dog\"",
+            "done 1 note \"sent by post\"",
+            "done 2",
+            "done-list",
+            "toggle 1",
+            "done-list",
+        ], &mut tl);
+        assert_eq!(out[3].as_deref(), Some("done"));
+        assert_eq!(out[5].as_deref(), Some("2 item(s) found\n1 \"file taxes\" #admin done-note \"sent by post\"\n2 \"walk dog\" "));
+        assert_eq!(out[7].as_deref(), Some("1 item(s) found\n2 \"walk dog\" ")); //reopening clears the note
+        assert_eq!(tl.get(Index::new(1)).unwrap().done_note, None);
+    }
+
+    #[test]
+    fn show_in_requested_order() {
+        let mut tl = TodoList::new();
+        let out = run_all(&[
+            "add \"buy milk\"",
+            "add \"file taxes\" #admin",
+            "add \"walk dog\"",
+            "done 2",
+            "show 1 9 0 2 1",
+        ], &mut tl);
+        //a done item is noted like a missing one, and an index given twice is shown twice
+        assert_eq!(out[4].as_deref(), Some("1 \"file taxes\" #admin\n9 not found\n0 \"buy milk\" \n2 not found\n1 \"file taxes\" #admin"));
+        let json = run_line("show 9 0", &mut tl).unwrap().to_lines(OutputFormat::JsonLines);
+        assert_eq!(json[0], "{\"Missing\":9}");
+        assert!(json[1].starts_with("{\"index\":0,"));
+    }
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn timings_by_query_kind() {
+        let mut tl = TodoList::new();
+        let mut stats = RunStats::new();
+        for line in ["add \"buy milk\"", "add \"walk dog\"", "search wa", "done 0", "done 7", "not a query"] {
+            run_line_timed(line, &mut tl, &RunnerConfig::default(), &mut stats);
+        }
+        let kinds: Vec<(&str, usize)> = stats.iter().map(|(name, count, _)| (name, count)).collect();
+        assert_eq!(kinds, vec![("add", 2), ("done", 2), ("search", 1)]); //the failed done counts, the unparsed line doesn't
+        assert_eq!(stats.count("toggle"), 0);
+        assert_eq!(stats.total("toggle"), std::time::Duration::ZERO);
+        assert!(stats.iter().all(|(name, _, total)| total == stats.total(name)));
+    }
+
+    #[test]
+    fn and_not_leaves_out_matches() {
+        fn check<T: TodoLister>(mut tl: T) {
+            let out = run_all(&[
+                "add \"fix login\" #bug",
+                "add \"fix logout\" #bug #wontfix",
+                "add \"fix layout\" #wontfix",
+                "add \"fix signup\" #bug",
+                "add \"call mum\"",
+                "search #bug :and-not #wontfix",
+                "search #bug",
+                "search #wontfix",
+                "search :not #wontfix",
+                "search fix :any #bug :and-not logout :and-not signup",
+            ], &mut tl);
+            assert_eq!(out[5].as_deref(), Some("2 item(s) found\n0 \"fix login\" #bug\n3 \"fix signup\" #bug"));
+            let found = |n: usize| out[n].as_deref().unwrap().lines().skip(1).map(String::from).collect::<Vec<String>>();
+            //the same as leaving the #wontfix results out of the #bug ones
+            let difference: Vec<String> = found(6).into_iter().filter(|line| !found(7).contains(line)).collect();
+            assert_eq!(found(5), difference);
+            //and as keeping the #bug results which the negated search for #wontfix finds
+            let both: Vec<String> = found(6).into_iter().filter(|line| found(8).contains(line)).collect();
+            assert_eq!(found(5), both);
+            assert_eq!(out[9].as_deref(), Some("2 item(s) found\n0 \"fix login\" #bug\n2 \"fix layout\" #wontfix"));
+        }
+        check(TodoList::new());
+        check(TriedoList::<RadixTrie>::new());
+    }
+
+    #[test]
+    fn search_escaped_hash_words() {
+        fn check<T: TodoLister>(mut tl: T) {
+            let out = run_all(&[
+                "add \"fix issue \\#12\" #bug",
+                "add \"fix issue \\#3\"",
+                "add \"use \\# in lists\"",
+                "search \\#12",
+                "search \\#",
+                "search issue 3",
+                "search \\#1 :prefix",
+            ], &mut tl);
+            assert_eq!(out[3].as_deref(), Some("1 item(s) found\n0 \"fix issue \\#12\" #bug"));
+            assert_eq!(out[4].as_deref().map(|o| o.lines().count()), Some(4));
+            assert_eq!(out[5].as_deref(), Some("1 item(s) found\n1 \"fix issue \\#3\" "));
+            assert_eq!(out[6].as_deref(), Some("1 item(s) found\n0 \"fix issue \\#12\" #bug"));
+        }
+        check(TodoList::new());
+        check(TodoList2::new());
+        check(TriedoList::<Trie1>::new());
+        check(TriedoList::<Trie2>::new());
+        check(TriedoList::<Trie3>::new());
+        check(TriedoList::<Trie4>::new());
+        check(TriedoList::<RadixTrie>::new());
+    }
+
+    #[test]
+    fn search_ordered_by_length() {
+        let mut tl = TriedoList::<Trie4>::new();
+        let out = run_all(&[
+            "add \"water the plants\"",
+            "add \"walk the dog today\"",
+            "add \"wash car\"",
+            "search wa :order-by length",
+            "search wa :order-by length-desc :limit 1",
+        ], &mut tl);
+        assert_eq!(out[3].as_deref(), Some("3 item(s) found\n2 \"wash car\" \n0 \"water the plants\" \n1 \"walk the dog today\" "));
+        assert_eq!(out[4].as_deref(), Some("1 item(s) found\n1 \"walk the dog today\" "));
+    }
+
+    #[test]
+    fn terms_with_their_own_modes() {
+        fn check<T: TodoLister>(mut tl: T) {
+            let out = run_all(&[
+                "add \"deploy production\"",
+                "add \"deployment process\"",
+                "add \"redeploy prod\"",
+                "add \"deploy preview\"",
+                "search exact:deploy prefix:prod",
+                "search deploy prefix:prod",
+                "search exact:deploy",
+            ], &mut tl);
+            assert_eq!(out[4].as_deref(), Some("1 item(s) found\n0 \"deploy production\" "));
+            assert_eq!(out[5].as_deref(), Some("2 item(s) found\n0 \"deploy production\" \n2 \"redeploy prod\" "));
+            assert_eq!(out[6].as_deref(), Some("2 item(s) found\n0 \"deploy production\" \n3 \"deploy preview\" "));
+        }
+        check(TodoList::new());
+        check(TodoList2::new());
+        check(TriedoList::<Trie4>::new());
+        check(TriedoList::<RadixTrie>::new());
+    }
+
+    #[test]
+    fn search_for_command_words() {
+        let mut tl = TriedoList::<Trie4>::new();
+        let out = run_all(&[
+            "add \"get it done\"",
+            "add \"search the attic\"",
+            "add \"add up receipts\"",
+            "done 0",
+            "search done",
+            "search done :include-done",
+            "search search :any add",
+            "search :not done",
+        ], &mut tl);
+        assert_eq!(out[4].as_deref(), Some("0 item(s) found")); //the only item with it is done
+        assert_eq!(out[5].as_deref(), Some("1 item(s) found\n0 \"get it done\" "));
+        assert_eq!(out[6].as_deref(), Some("2 item(s) found\n1 \"search the attic\" \n2 \"add up receipts\" "));
+        assert_eq!(out[7].as_deref(), Some("2 item(s) found\n1 \"search the attic\" \n2 \"add up receipts\" "));
+    }
+
+    #[test]
+    fn done_list_in_index_order() {
+        fn check<T: TodoLister>(mut tl: T) {
+            let out = run_all(&[
+                "add \"buy milk\"",
+                "add \"file taxes\" #admin",
+                "add \"walk dog\"",
+                "done-list",
+                "done 2",
+                "done 0",
+                "done-list",
+                "search buy",
+            ], &mut tl);
+            assert_eq!(out[3].as_deref(), Some("0 item(s) found"));
+            assert_eq!(out[6].as_deref(), Some("2 item(s) found\n0 \"buy milk\" \n2 \"walk dog\" "));
+            assert_eq!(out[7].as_deref(), Some("0 item(s) found"));
+        }
+        check(TodoList::new());
+        check(TodoList2::new());
+        check(TriedoList::<Trie4>::new());
+        check(TriedoList::<RadixTrie>::new().with_done_policy(DonePolicy::MoveToArchive));
+    }
+
+    #[test]
+    fn bump_saturates() {
+        let mut tl = TodoList::new();
+        let out = run_all(&[
+            "add \"buy milk\" priority 3",
+            "bump 0 +2",
+            "bump 0 -10",
+            "bump 0 +200",
+            "bump 0 +100",
+            "bump 0 -99999999999",
+            "bump 0 +99999999999",
+            "bump 5 +1",
+        ], &mut tl);
+        assert_eq!(out[1..7].iter().map(|o| o.as_deref().unwrap()).collect::<Vec<&str>>(),
+            vec!["priority 5", "priority 0", "priority 200", "priority 255", "priority 0", "priority 255"]);
+        assert_eq!(out[7], None);
     }
 }
@@ -1,11 +1,16 @@
 use std::fmt;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::cell::RefCell;
 use std::mem;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+use rustc_hash::{FxHashSet, FxHashMap};
+use serde::{Serialize, Deserialize};
 
 use crate::*;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash, Serialize, Deserialize)]
 pub struct Index(u64);
 impl Index {
     pub fn new(i: u64) -> Self {
@@ -27,25 +32,49 @@ impl fmt::Display for Index {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")] //so loaded words are checked like new ones
 pub struct Word(String);
 impl Word {
-    pub fn new(s: &str) -> Self {
-        Word(s.to_owned())
+    //words are separated by spaces when displayed, so a word containing whitespace (or an empty word) 
+    //could not be displayed and parsed back as the same description, these are rejected with None
+    pub fn new(s: &str) -> Option<Self> {
+        if s.is_empty() || s.chars().any(char::is_whitespace) {
+            None
+        }
+        else {
+            Some(Word(s.to_owned()))
+        }
     }
 
     pub fn value(&self) -> &str {
         &self.0
     }
 }
+impl TryFrom<String> for Word {
+    type Error = QueryError;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Word::new(&s).ok_or_else(|| QueryError(format!("\"{}\" is not a single word", s)))
+    }
+}
 impl fmt::Display for Word {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
 pub struct Tag(String);
+//the text a tag (or a search term for one) is matched on, see Tag::key
+pub(crate) fn tag_key(text: &str) -> Cow<'_, str> {
+    if text.chars().any(char::is_uppercase) {
+        Cow::Owned(text.to_lowercase())
+    }
+    else {
+        Cow::Borrowed(text)
+    }
+}
 impl Tag {
     pub fn new(s: &str) -> Self {
         Tag(s.to_owned())
@@ -55,28 +84,101 @@ impl Tag {
         &self.0
     }
 
-    pub fn from_strings(ss: Vec<&str>) -> Vec<Tag> {
-        ss.clone().into_iter().map(|s| Tag::new(s)).collect()
+    //tags are matched case-insensitively on this lowercased text, the original casing is only for display
+    pub fn key(&self) -> Cow<'_, str> {
+        tag_key(&self.0)
+    }
+
+    pub fn from_strings<'a>(ss: impl IntoIterator<Item = &'a str>) -> Vec<Tag> {
+        ss.into_iter().map(Tag::new).collect()
+    }
+}
+//the parser's checks, a tag has to be more than whitespace and can't contain the quote that would end it when displayed
+impl TryFrom<String> for Tag {
+    type Error = QueryError;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        if s.trim().is_empty() || s.contains('"') {
+            Err(QueryError(format!("\"{}\" is not a valid tag", s)))
+        }
+        else {
+            Ok(Tag(s))
+        }
     }
 }
 impl fmt::Display for Tag {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "#{}", self.0)
+        //tags which couldn't be parsed as a plain word are quoted, so they can be parsed back
+        if !self.0.is_empty() && self.0.chars().all(|c| c.is_ascii_alphabetic() || c == '-' || c == '/') {
+            write!(f, "#{}", self.0)
+        }
+        else {
+            write!(f, "#\"{}\"", self.0)
+        }
+    }
+}
+
+//a calendar date, displayed like 2024-01-31
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+impl Date {
+    //None unless the day exists in that month (taking leap years into account)
+    #[allow(clippy::manual_is_multiple_of)] //is_multiple_of needs a much newer toolchain than the rest of the crate
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        if day == 0 || day > days_in_month {
+            return None
+        }
+        Some(Date { year, month, day })
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+}
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
     }
 }
 
 //custom display implementation for a Vec of Words
 struct Words<'a> {
     arr: &'a Vec<Word>,
+    limit: Option<usize>, //words after this many are left out, with ... marking that some were
 }
 impl fmt::Display for Words<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut display_string = String::new();
-        for word in self.arr {
+        for word in self.arr.iter().take(self.limit.unwrap_or(usize::MAX)) {
+            if word.0.starts_with('#') {
+                display_string.push('\\'); //escaped as it's parsed, so it isn't taken for a tag
+            }
             display_string.push_str(&word.to_string());
             display_string.push_str(" ");
         }
         display_string.pop();
+        if matches!(self.limit, Some(limit) if self.arr.len() > limit) {
+            display_string.push_str("...");
+        }
         write!(f, "{}", display_string)
     }
 }
@@ -97,20 +199,120 @@ impl fmt::Display for Tags<'_> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TodoItem {
     pub index: Index,
     pub description: Vec<Word>,
     pub tags: Vec<Tag>,
     pub done: bool,
+    pub note: Option<String>, //freeform text, not indexed by the tries
+    pub priority: u8,
+    pub due: Option<Date>,
+    pub done_note: Option<String>, //how the item was resolved, given when it's marked done and cleared if it's reopened
+    pub completed: Option<u64>, //numbers the list's completions in the order they happened (there's no clock), None unless done
 }
 impl TodoItem {
+    //replaces the old tag with the new one, or just removes the old one if another of the item's tags already has the new one's key
+    //so no two tags differing only in case are left, returns false if the item doesn't have the old tag
+    fn retag(&mut self, old: &Tag, new: Tag) -> bool {
+        let (old_key, new_key) = (old.key(), new.key());
+        match self.tags.iter().position(|t| t.key() == old_key) {
+            None => false,
+            Some(n) => {
+                if self.tags.iter().enumerate().any(|(m, t)| m != n && t.key() == new_key) {
+                    self.tags.remove(n);
+                }
+                else {
+                    self.tags[n] = new;
+                }
+                true
+            },
+        }
+    }
+    //whether the item has the tag, ignoring case like searches do
+    pub fn has_tag(&self, tag: &Tag) -> bool {
+        let key = tag.key();
+        self.tags.iter().any(|t| t.key() == key)
+    }
+    //adjusts priority by delta, saturating at the bounds of u8, returns the new priority
+    fn bump_priority(&mut self, delta: i32) -> u8 {
+        self.priority = (self.priority as i32).saturating_add(delta).max(u8::MIN as i32).min(u8::MAX as i32) as u8;
+        self.priority
+    }
+    //records how the item was resolved, returns false (leaving it unchanged) unless it's done
+    fn set_done_note(&mut self, note: Option<String>) -> bool {
+        if self.done {
+            self.done_note = note;
+        }
+        self.done
+    }
+    //whether the item's note contains the text as it is, an item without a note never does
+    pub fn note_contains(&self, text: &str) -> bool {
+        matches!(&self.note, Some(note) if note.contains(text))
+    }
     pub fn new(index: Index, description: Vec<Word>, tags: Vec<Tag>, done: bool) -> Self {
         TodoItem {
             index,
             description,
             tags,
             done,
+            note: None,
+            priority: 0,
+            due: None,
+            done_note: None,
+            completed: None,
+        }
+    }
+}
+//builds a TodoItem by naming its fields, anything not set is empty, not done, without a note or due date, and priority 0
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TodoItemBuilder {
+    description: Vec<Word>,
+    tags: Vec<Tag>,
+    done: bool,
+    note: Option<String>,
+    priority: u8,
+    due: Option<Date>,
+}
+impl TodoItemBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn description(mut self, description: Vec<Word>) -> Self {
+        self.description = description;
+        self
+    }
+    pub fn tags(mut self, tags: Vec<Tag>) -> Self {
+        self.tags = tags;
+        self
+    }
+    pub fn done(mut self, done: bool) -> Self {
+        self.done = done;
+        self
+    }
+    pub fn note(mut self, note: &str) -> Self {
+        self.note = Some(note.to_owned());
+        self
+    }
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+    pub fn due(mut self, due: Date) -> Self {
+        self.due = Some(due);
+        self
+    }
+    pub fn build(self, index: Index) -> TodoItem {
+        TodoItem {
+            index,
+            description: self.description,
+            tags: self.tags,
+            done: self.done,
+            note: self.note,
+            priority: self.priority,
+            due: self.due,
+            done_note: None,
+            completed: None, //there's no telling when an item built as done was done
         }
     }
 }
@@ -119,6 +321,8 @@ impl PartialOrd for TodoItem {
         self.index.partial_cmp(&other.index)
     }
 }
+//items are ordered by index alone, so sorting them by anything else (e.g. priority) should finish with
+//.then_with(|| a.cmp(b)) to keep equal items in ascending index order on every run
 impl Ord for TodoItem {
     fn cmp(&self, other: &Self) -> Ordering {
         self.index.cmp(&other.index)
@@ -126,57 +330,443 @@ impl Ord for TodoItem {
 }
 impl fmt::Display for TodoItem {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} \"{}\" {}", self.index, Words{arr: &self.description}, Tags{arr: &self.tags})
+        self.fmt_with_limit(f, None)
+    }
+}
+impl TodoItem {
+    //displays the item like usual but with only the first k words of its description, for compact listings
+    //at least one word is kept, so a description is never shown as just the ellipsis
+    pub fn display_truncated(&self, k: usize) -> TruncatedItem<'_> {
+        TruncatedItem { item: self, k: k.max(1) }
+    }
+    fn fmt_with_limit(&self, f: &mut fmt::Formatter, limit: Option<usize>) -> fmt::Result {
+        write!(f, "{} \"{}\" {}", self.index, Words{arr: &self.description, limit}, Tags{arr: &self.tags})?;
+        //optional fields are only shown when set, so items without them display as they always have
+        let mut extras = Vec::new();
+        if self.priority > 0 {
+            extras.push(format!("priority {}", self.priority));
+        }
+        if let Some(due) = self.due {
+            extras.push(format!("due {}", due));
+        }
+        if let Some(note) = &self.note {
+            extras.push(format!("note \"{}\"", note));
+        }
+        if let Some(done_note) = &self.done_note {
+            extras.push(format!("done-note \"{}\"", done_note));
+        }
+        if !extras.is_empty() {
+            write!(f, "{}{}", if self.tags.is_empty() { "" } else { " " }, extras.join(" "))?;
+        }
+        Ok(())
+    }
+}
+//see TodoItem::display_truncated, a description of k words or fewer is shown in full
+pub struct TruncatedItem<'a> {
+    item: &'a TodoItem,
+    k: usize,
+}
+impl fmt::Display for TruncatedItem<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.item.fmt_with_limit(f, Some(self.k))
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SearchWordOrTag {
     RawWord (String),
-    RawTag (String),
+    RawTag (String), //an empty tag matches any item which has at least one tag
+    RawAny (String), //matches a description word or a tag
+    WithMode (MatchMode, Box<SearchWordOrTag>), //a term matched with its own mode rather than the search's
+}
+impl SearchWordOrTag {
+    //the term without any mode of its own, and that mode (the outermost one if there are several)
+    pub fn split_mode(&self) -> (&SearchWordOrTag, Option<MatchMode>) {
+        match self {
+            SearchWordOrTag::WithMode(mode, term) => (term.split_mode().0, Some(*mode)),
+            term => (term, None),
+        }
+    }
+}
+
+//checks items loaded from elsewhere are in strictly ascending index order, returning the next index to use
+fn next_index(items: &[TodoItem]) -> Result<u64, QueryError> {
+    for pair in items.windows(2) {
+        if pair[0].index >= pair[1].index {
+            return Err(QueryError(format!("Item indices must be unique and ascending, found {} before {}", pair[0].index, pair[1].index)))
+        }
+    }
+    Ok(items.last().map_or(0, |item| item.index.value() + 1))
+}
+
+//the latest completion among items loaded from elsewhere, so later completions are numbered after it
+fn last_completion(items: &[TodoItem]) -> u64 {
+    items.iter().filter_map(|item| item.completed).max().unwrap_or(0)
+}
+
+//gives items contiguous indices from 0 in their current order, returning the old index of each mapped to its new one
+fn reindex_items<'a>(items: impl Iterator<Item = &'a mut TodoItem>) -> FxHashMap<Index, Index> {
+    let mut mapping = FxHashMap::default();
+    for (n, item) in items.enumerate() {
+        let new = Index::new(n as u64);
+        mapping.insert(item.index, new);
+        item.index = new;
+    }
+    mapping
+}
+
+//exchanges the indices of two items, moving each into the other's position so the items stay in index order
+//returns false (changing nothing) unless both are there
+fn swap_items(items: &mut [TodoItem], a: Index, b: Index) -> bool {
+    let (Ok(na), Ok(nb)) = (items.binary_search_by_key(&a, |item| item.index), items.binary_search_by_key(&b, |item| item.index)) else {
+        return false
+    };
+    items.swap(na, nb);
+    items[na].index = a;
+    items[nb].index = b;
+    true
+}
+//where an index refers to after swap_items
+fn swapped_index(idx: Index, a: Index, b: Index) -> Index {
+    if idx == a { b } else if idx == b { a } else { idx }
+}
+
+//approximate bytes used by the items, each item's own size plus the text of its words, tags and note
+fn items_memory<'a>(items: impl IntoIterator<Item = &'a TodoItem>) -> usize {
+    items.into_iter().map(|item| {
+        mem::size_of::<TodoItem>()
+            + item.description.iter().map(|Word(w)| mem::size_of::<Word>() + w.len()).sum::<usize>()
+            + item.tags.iter().map(|Tag(t)| mem::size_of::<Tag>() + t.len()).sum::<usize>()
+            + item.note.as_ref().map_or(0, String::len)
+            + item.done_note.as_ref().map_or(0, String::len)
+    }).sum()
+}
+
+//the item with the index, from items in ascending index order
+fn item_mut(items: &mut [TodoItem], idx: Index) -> Option<&mut TodoItem> {
+    let n = items.binary_search_by_key(&idx, |item| item.index).ok()?;
+    Some(&mut items[n])
 }
 
+//the page of the items which aren't done and their count, going through the items rather than collecting a search of them all
+fn live_page(items: &[TodoItem], offset: usize, limit: usize) -> (Vec<&TodoItem>, usize) {
+    let live = || items.iter().filter(|item| !item.done);
+    (live().skip(offset).take(limit).collect(), live().count())
+}
+
+//collects the tags of every item which isn't done, sorted and deduplicated by key
+//tags differing only in case are given once, as the lowest index item has it
+fn live_tags(items: &[TodoItem]) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = items.iter()
+        .filter(|item| !item.done)
+        .flat_map(|item| item.tags.iter().cloned())
+        .collect();
+    tags.sort_by(|a, b| a.key().cmp(&b.key())); //stable, so the first of each key is still the lowest index's
+    tags.dedup_by(|a, b| a.key() == b.key());
+    tags
+}
+
+//the changes between two snapshots of a list, each as indices in ascending order
+//an item which was completed and also changed in some other way is in both completed and edited
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ListDiff {
+    pub added: Vec<Index>,
+    pub removed: Vec<Index>,
+    pub completed: Vec<Index>,
+    pub edited: Vec<Index>, //any other change, including being marked not done again
+}
+impl ListDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.completed.is_empty() && self.edited.is_empty()
+    }
+}
+//compares items which are both in ascending index order
+fn diff_items(before: &[TodoItem], after: &[TodoItem]) -> ListDiff {
+    let mut diff = ListDiff::default();
+    let (mut before, mut after) = (before.iter().peekable(), after.iter().peekable());
+    loop {
+        match (before.peek(), after.peek()) {
+            (Some(b), Some(a)) if b.index == a.index => {
+                if !b.done && a.done {
+                    diff.completed.push(a.index);
+                }
+                let changed = b.description != a.description || b.tags != a.tags || b.note != a.note
+                    || b.priority != a.priority || b.due != a.due || b.done_note != a.done_note;
+                if changed || (b.done && !a.done) {
+                    diff.edited.push(a.index);
+                }
+                before.next();
+                after.next();
+            },
+            (Some(b), Some(a)) if b.index < a.index => {
+                diff.removed.push(b.index);
+                before.next();
+            },
+            (Some(b), None) => {
+                diff.removed.push(b.index);
+                before.next();
+            },
+            (_, Some(a)) => {
+                diff.added.push(a.index);
+                after.next();
+            },
+            (None, None) => return diff,
+        }
+    }
+}
+
+//what find_duplicates groups items by: their description and their sorted tag keys
+type DuplicateKey<'a> = (&'a [Word], Vec<Cow<'a, str>>);
+
 pub trait TodoLister {
     fn push(&mut self, description: Vec<Word>, tags: Vec<Tag>) -> TodoItem;
     fn done_with_index(&mut self, idx: Index) -> Option<Index>;
-    fn search(&self, sp: SearchParams) -> Vec<&TodoItem>;
+    fn done_by_raw(&mut self, n: u64) -> Option<Index> {
+        self.done_with_index(Index::new(n))
+    }
+    fn toggle_with_index(&mut self, idx: Index) -> Option<bool>; //returns whether the item is now done
+    fn undo_last_done(&mut self) -> Option<Index>; //marks the most recently completed item as not done, if it still is
+    #[must_use]
+    fn search(&self, sp: SearchParams) -> Vec<&TodoItem>; //each matching item appears once, in ascending index order
+    //clones the results, for keeping them after the list is borrowed mutably or dropped
+    #[must_use]
+    fn search_owned(&self, sp: SearchParams) -> Vec<TodoItem> {
+        self.search(sp).into_iter().cloned().collect()
+    }
+    //the nth (from 0) of the results search would return, only looking up that one where the implementation allows
+    #[must_use]
+    fn search_nth(&self, sp: SearchParams, n: usize) -> Option<&TodoItem> {
+        if matches!(sp.limit, Some(limit) if n >= limit) {
+            return None
+        }
+        let offset = sp.offset.checked_add(n)?;
+        self.search(sp.with_offset(offset).with_limit(1)).into_iter().next()
+    }
+    //like search, but only considering the items at these indices, for drilling down into an earlier result
+    //the offset and limit apply to the narrowed results rather than to the whole search's
+    #[must_use]
+    fn search_within(&self, sp: SearchParams, indices: &[Index]) -> Vec<&TodoItem> {
+        let mut within = indices.to_vec();
+        within.sort_unstable();
+        let results = self.search(SearchParams { offset: 0, limit: None, ..sp.clone() });
+        sp.paginate(results.into_iter().filter(|item| within.binary_search(&item.index).is_ok()))
+    }
+    //the item which isn't done with the most words in its description, the lowest index of those with equally many
+    fn longest_description(&self) -> Option<&TodoItem> {
+        self.search(SearchParams::new(Vec::new())).into_iter()
+            .fold(None, |longest: Option<&TodoItem>, item| match longest {
+                Some(l) if l.description.len() >= item.description.len() => Some(l), //search gives ascending indices
+                _ => Some(item),
+            })
+    }
+    //a page of the items which aren't done, in ascending index order, with how many such items there are in all
+    #[must_use]
+    fn page(&self, offset: usize, limit: usize) -> (Vec<&TodoItem>, usize);
+    fn retag_with_index(&mut self, idx: Index, old: Tag, new: Tag) -> Option<Index>;
+    fn clear_tags(&mut self, idx: Index) -> Option<usize>; //removes every tag from the item (done or not), returns how many it had
+    fn edit(&mut self, idx: Index, description: Vec<Word>) -> Option<Vec<Word>>; //returns the replaced description
+    fn set_note(&mut self, idx: Index, note: Option<String>) -> Option<Index>;
+    //records how a done item was resolved, returns None unless the item is done
+    fn set_done_note(&mut self, idx: Index, note: Option<String>) -> Option<Index>;
+    fn set_due(&mut self, idx: Index, due: Option<Date>) -> Option<Index>;
+    fn bump_priority(&mut self, idx: Index, delta: i32) -> Option<u8>; //returns the new priority
+    #[must_use]
+    fn search_note(&self, text: &str) -> Vec<&TodoItem> { //contiguous substring match, rather than subsequence
+        self.search(SearchParams::new(Vec::new())).into_iter().filter(|item| item.note_contains(text)).collect()
+    }
+    fn all_tags(&self) -> Vec<Tag>; //sorted and deduplicated, only from items which aren't done
+    //items which aren't done that have exactly this tag (or don't), in ascending index order
+    fn items_with_tag(&self, tag: &Tag) -> Vec<&TodoItem> {
+        self.search(SearchParams::new(Vec::new())).into_iter().filter(|item| item.has_tag(tag)).collect()
+    }
+    fn items_without_tag(&self, tag: &Tag) -> Vec<&TodoItem> {
+        self.search(SearchParams::new(Vec::new())).into_iter().filter(|item| !item.has_tag(tag)).collect()
+    }
+    //the n tags on the most items which aren't done, with how many items have each, by count descending and then by name
+    //tags differing only in case are counted together, shown as the lowest index item has it
+    fn top_tags(&self, n: usize) -> Vec<(Tag, usize)> {
+        let mut counts: FxHashMap<Cow<'_, str>, (&Tag, usize)> = FxHashMap::default();
+        for item in self.search(SearchParams::new(Vec::new())) {
+            for tag in &item.tags {
+                counts.entry(tag.key()).or_insert((tag, 0)).1 += 1;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(key_a, (_, a)), (key_b, (_, b))| b.cmp(a).then_with(|| key_a.cmp(key_b)));
+        counts.into_iter().take(n).map(|(_, (tag, count))| (tag.clone(), count)).collect()
+    }
+    //groups of items which aren't done with the same description and tags (tags in any order or case), only groups of more than one
+    //each group is in ascending index order, and the groups are in order of their first index
+    fn find_duplicates(&self) -> Vec<Vec<Index>> {
+        let mut groups: FxHashMap<DuplicateKey<'_>, Vec<Index>> = FxHashMap::default();
+        for item in self.search(SearchParams::new(Vec::new())) {
+            let mut keys: Vec<_> = item.tags.iter().map(Tag::key).collect();
+            keys.sort();
+            keys.dedup();
+            groups.entry((&item.description[..], keys)).or_default().push(item.index);
+        }
+        let mut duplicates: Vec<Vec<Index>> = groups.into_values().filter(|group| group.len() > 1).collect();
+        duplicates.sort();
+        duplicates
+    }
+    fn done_items(&self) -> Vec<&TodoItem>; //in ascending index order
+    //the n items done most recently, latest first, items loaded already done without a completion number come last
+    fn recently_done(&self, n: usize) -> Vec<&TodoItem> {
+        let mut items = self.done_items();
+        items.sort_by_key(|item| Reverse(item.completed)); //stable, so the unnumbered ones stay in index order
+        items.truncate(n);
+        items
+    }
+    fn retain<F: FnMut(&TodoItem) -> bool>(&mut self, f: F);
+    //splits the list into the items f keeps and the rest, e.g. active and done
+    //both halves keep the next index, so items pushed to either don't reuse an index from the other
+    fn partition<F: Fn(&TodoItem) -> bool>(mut self, f: F) -> (Self, Self) where Self: Sized + Clone {
+        let mut rest = self.clone();
+        self.retain(|item| f(item));
+        rest.retain(|item| !f(item));
+        (self, rest)
+    }
+    //renumbers every item (done or not) from 0 in order, so there are no gaps left by retain, returning each old index's new one
+    fn reindex(&mut self) -> FxHashMap<Index, Index>;
+    //exchanges the indices of two items (done or not), returns false unless both exist
+    fn swap(&mut self, a: Index, b: Index) -> bool;
+    fn reset(&mut self); //removes every item, the next item pushed gets the first index again
+    fn get(&self, idx: Index) -> Option<&TodoItem>;
+    //looks up each index in order, giving None where there's no item or it is done (unlike get)
+    fn get_many(&self, idxs: &[Index]) -> Vec<Option<&TodoItem>> {
+        idxs.iter().map(|idx| self.get(*idx).filter(|item| !item.done)).collect()
+    }
+    fn len(&self) -> usize; //includes done items
+    //approximate bytes used by the list, counting the items (done or not) and any search structures, but not spare capacity
+    fn memory_usage(&self) -> usize;
+    fn next_index(&self) -> Index; //the index the next push will give its item, without changing anything
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn view(&self) -> TodoView<'_, Self> where Self: Sized {
+        TodoView { list: self }
+    }
+}
+
+//borrows a list without access to the mutating methods, for code which should only read it
+#[derive(Debug)]
+pub struct TodoView<'a, L: TodoLister> {
+    list: &'a L,
+}
+impl<'a, L: TodoLister> TodoView<'a, L> {
+    #[must_use]
+    pub fn search(&self, sp: SearchParams) -> Vec<&'a TodoItem> {
+        self.list.search(sp)
+    }
+    #[must_use]
+    pub fn search_within(&self, sp: SearchParams, indices: &[Index]) -> Vec<&'a TodoItem> {
+        self.list.search_within(sp, indices)
+    }
+    pub fn get(&self, idx: Index) -> Option<&'a TodoItem> {
+        self.list.get(idx)
+    }
+    pub fn get_many(&self, idxs: &[Index]) -> Vec<Option<&'a TodoItem>> {
+        self.list.get_many(idxs)
+    }
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TodoList {
     items: Vec<TodoItem>,
+    top_index: u64,
+    last_done: Option<Index>, //the item most recently marked done, for undo_last_done
+    completions: u64, //the number given to the latest item done, see TodoItem::completed
 }
 impl TodoList {
     pub fn new() -> Self {
         TodoList {
             items: Vec::new(),
+            top_index: 0,
+            last_done: None,
+            completions: 0,
         }
     }
-    fn match_subsequence(sequence: &str, subsequence: &str) -> bool {
-        let mut sub_index = 0;
-        let sub_bytes = subsequence.as_bytes(); //this only splits on exact characters when we're using ASCII, not unicode
-        for byte in sequence.as_bytes().iter() {
-            if sub_index == subsequence.len() {
-                return true
-            }
-            unsafe { //safe because termination is guaranteed before index gets too large
-                if byte == sub_bytes.get_unchecked(sub_index) {
-                    sub_index += 1;
-                }
-            }
+    //room for n items before the list has to reallocate
+    pub fn with_capacity(n: usize) -> Self {
+        TodoList {
+            items: Vec::with_capacity(n),
+            ..Self::new()
+        }
+    }
+    pub fn reserve(&mut self, n: usize) {
+        self.items.reserve(n);
+    }
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+    pub fn from_items(items: Vec<TodoItem>) -> Result<Self, QueryError> {
+        let top_index = next_index(&items)?;
+        let completions = last_completion(&items);
+        Ok(TodoList {
+            items,
+            top_index,
+            last_done: None,
+            completions,
+        })
+    }
+    //what changed from this snapshot of the list to the other one
+    pub fn diff(&self, other: &Self) -> ListDiff {
+        diff_items(&self.items, &other.items)
+    }
+    //whether any word in the description (or tag) matches the search parameter
+    pub(crate) fn match_param(item: &TodoItem, param: &SearchWordOrTag, sp: &SearchParams) -> bool {
+        let (param, own_mode) = param.split_mode();
+        let (mode, words_ic, tags_ic) = (own_mode.unwrap_or(sp.mode), sp.words_ignore_case, sp.tags_ignore_case);
+        match param {
+            SearchWordOrTag::RawWord(sw) => item.description.iter().any(|Word(w)| matching::match_word(w, sw, mode, words_ic)),
+            SearchWordOrTag::RawTag(st) if st.is_empty() => !item.tags.is_empty(), //a bare # means the item has some tag
+            SearchWordOrTag::RawTag(st) => item.tags.iter().any(|t| matching::match_word(&t.key(), &tag_key(st), sp.term_tag_mode(st, own_mode), tags_ic)),
+            SearchWordOrTag::RawAny(s) => item.description.iter().any(|Word(w)| matching::match_word(w, s, mode, words_ic))
+                || item.tags.iter().any(|t| matching::match_word(&t.key(), &tag_key(s), sp.term_tag_mode(s, own_mode), tags_ic)),
+            SearchWordOrTag::WithMode(..) => false, //split_mode doesn't leave any
         }
-        sub_index == subsequence.len()
+    }
+}
+//compact binary storage (packed CBOR), much smaller than the JSON output since field names aren't stored
+#[cfg(feature = "binary")]
+impl TodoList {
+    pub fn save_bin(&self) -> Vec<u8> {
+        serde_cbor::ser::to_vec_packed(&(self.top_index, self.last_done, &self.items)).unwrap() //serializing plain data to memory can't fail
+    }
+    //checks the items like from_items, the next index pushed (and the completion undo_last_done reverses) are kept
+    pub fn load_bin(bytes: &[u8]) -> Result<Self, QueryError> {
+        let (top_index, last_done, items): (u64, Option<Index>, Vec<TodoItem>) = serde_cbor::from_slice(bytes)
+            .map_err(|e| QueryError(format!("Could not read binary list: {}", e)))?;
+        let mut tl = Self::from_items(items)?;
+        if top_index < tl.top_index {
+            return Err(QueryError(String::from("Binary list's next index is lower than one of its items")))
+        }
+        tl.top_index = top_index;
+        tl.last_done = last_done;
+        Ok(tl)
     }
 }
 impl TodoLister for TodoList {
     fn push(&mut self, description: Vec<Word>, tags: Vec<Tag>) -> TodoItem {
-        let item = TodoItem::new(Index::new(self.items.len() as u64), description, tags, false);
+        let item = TodoItem::new(Index::new(self.top_index), description, tags, false);
         let item_c = item.clone();
         self.items.push(item);
+        self.top_index += 1;
         item_c
     }
     fn done_with_index(&mut self, idx: Index) -> Option<Index> {
         if let Ok(n) = self.items.binary_search_by_key(&idx, |item| item.index) {
+            if !self.items[n].done {
+                self.last_done = Some(idx);
+                self.completions += 1;
+                self.items[n].completed = Some(self.completions);
+            }
             self.items[n].done = true;
             Some(idx)
         }
@@ -184,35 +774,118 @@ impl TodoLister for TodoList {
             None
         }
     }
+    fn toggle_with_index(&mut self, idx: Index) -> Option<bool> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        self.items[n].done = !self.items[n].done;
+        if self.items[n].done {
+            self.last_done = Some(idx);
+            self.completions += 1;
+            self.items[n].completed = Some(self.completions);
+        }
+        else {
+            self.items[n].done_note = None;
+            self.items[n].completed = None;
+        }
+        Some(self.items[n].done)
+    }
+    fn undo_last_done(&mut self) -> Option<Index> {
+        let idx = self.last_done.take()?;
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        if !self.items[n].done {
+            return None
+        }
+        self.items[n].done = false;
+        self.items[n].done_note = None;
+        self.items[n].completed = None;
+        Some(idx)
+    }
     fn search(&self, sp: SearchParams) -> Vec<&TodoItem> {
         let mut results = Vec::new();
         'item: for item in self.items.iter() { 
-            if item.done { //don't search done items
+            if !sp.keeps(item) { //done items are skipped unless the search includes them
                 continue 'item
             }
-            'param: for param in &sp.params { 
-                match param {
-                    SearchWordOrTag::RawWord(sw) => {
-                        for Word(w) in &item.description {
-                            if Self::match_subsequence(w, sw) {
-                                continue 'param //successful match, try next search parameter
-                            }
-                        }
-                        continue 'item //failed to match with any word in description, try next item
-                    }
-                    SearchWordOrTag::RawTag(st) => {
-                        for Tag(t) in &item.tags {
-                            if Self::match_subsequence(t, st) {
-                                continue 'param //successful match, try next search parameter
-                            }
-                        }
-                        continue 'item //failed to match with any tag, try next item
-                    }
-                }
+            let matched = sp.combines(|param| Self::match_param(item, param, &sp)); //stops at the first term which settles it
+            if matched != sp.negate { //pushed at most once however many of its words matched
+                results.push(item);
             }
-            results.push(item); //successfully matched every seach parameter, add to results
         }
-        results
+        sp.paginate(results)
+    }
+    fn retag_with_index(&mut self, idx: Index, old: Tag, new: Tag) -> Option<Index> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        if self.items[n].retag(&old, new) {
+            Some(idx)
+        }
+        else {
+            None
+        }
+    }
+    fn bump_priority(&mut self, idx: Index, delta: i32) -> Option<u8> {
+        Some(item_mut(&mut self.items, idx)?.bump_priority(delta))
+    }
+    fn clear_tags(&mut self, idx: Index) -> Option<usize> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        Some(mem::take(&mut self.items[n].tags).len())
+    }
+    fn edit(&mut self, idx: Index, description: Vec<Word>) -> Option<Vec<Word>> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        Some(mem::replace(&mut self.items[n].description, description))
+    }
+    fn set_note(&mut self, idx: Index, note: Option<String>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.note = note;
+        Some(idx)
+    }
+    fn set_done_note(&mut self, idx: Index, note: Option<String>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.set_done_note(note).then_some(idx)
+    }
+    fn set_due(&mut self, idx: Index, due: Option<Date>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.due = due;
+        Some(idx)
+    }
+    fn get(&self, idx: Index) -> Option<&TodoItem> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        Some(&self.items[n])
+    }
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+    fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + items_memory(&self.items)
+    }
+    fn next_index(&self) -> Index {
+        Index::new(self.top_index)
+    }
+    fn done_items(&self) -> Vec<&TodoItem> {
+        self.items.iter().filter(|item| item.done).collect()
+    }
+    fn page(&self, offset: usize, limit: usize) -> (Vec<&TodoItem>, usize) {
+        live_page(&self.items, offset, limit)
+    }
+    fn all_tags(&self) -> Vec<Tag> {
+        live_tags(&self.items)
+    }
+    fn retain<F: FnMut(&TodoItem) -> bool>(&mut self, f: F) {
+        self.items.retain(f);
+    }
+    fn reindex(&mut self) -> FxHashMap<Index, Index> {
+        let mapping = reindex_items(self.items.iter_mut());
+        self.top_index = self.items.len() as u64;
+        self.last_done = self.last_done.and_then(|idx| mapping.get(&idx).copied());
+        mapping
+    }
+    fn swap(&mut self, a: Index, b: Index) -> bool {
+        if !swap_items(&mut self.items, a, b) {
+            return false
+        }
+        self.last_done = self.last_done.map(|idx| swapped_index(idx, a, b));
+        true
+    }
+    fn reset(&mut self) {
+        self.items.clear();
+        self.top_index = 0;
+        self.last_done = None;
+        self.completions = 0;
     }
 }
 
@@ -221,102 +894,139 @@ impl TodoLister for TodoList {
 pub struct TodoList2 {
     items: Vec<TodoItem>,
     item_refs: RefCell<Option<Vec<usize>>>,
+    top_index: u64,
+    last_done: Option<Index>,
+    completions: u64,
 }
 impl TodoList2 {
     pub fn new() -> Self {
         TodoList2 {
             items: Vec::new(),
             item_refs: RefCell::new(Some(Vec::new())),
+            top_index: 0,
+            last_done: None,
+            completions: 0,
+        }
+    }
+    //room for n items (and search results) before the list has to reallocate
+    pub fn with_capacity(n: usize) -> Self {
+        TodoList2 {
+            items: Vec::with_capacity(n),
+            item_refs: RefCell::new(Some(Vec::with_capacity(n))),
+            ..Self::new()
+        }
+    }
+    pub fn reserve(&mut self, n: usize) {
+        self.items.reserve(n);
+        if let Some(item_refs) = self.item_refs.get_mut() {
+            item_refs.reserve(self.items.len() + n); //results can include every item
         }
     }
-    fn search_initial<'a>(&'a self, item_refs: &mut Vec<&'a TodoItem>, search: SearchWordOrTag) {
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+    pub fn from_items(items: Vec<TodoItem>) -> Result<Self, QueryError> {
+        let top_index = next_index(&items)?;
+        let completions = last_completion(&items);
+        Ok(TodoList2 {
+            items,
+            item_refs: RefCell::new(Some(Vec::new())),
+            top_index,
+            last_done: None,
+            completions,
+        })
+    }
+    fn search_initial<'a>(&'a self, item_refs: &mut Vec<&'a TodoItem>, search: SearchWordOrTag, sp: &SearchParams) {
         match search {
             SearchWordOrTag::RawWord(subsequence) => {
                 for item in &self.items {
-                    if item.done {
+                    if !sp.keeps(item) {
                         continue
                     }
                     for Word(sequence) in &item.description {
-                        if Self::match_subsequence(&sequence, &subsequence) {
+                        if matching::match_word(&sequence, &subsequence, sp.mode, sp.words_ignore_case) {
                             item_refs.push(item);
                             break
                         }
                     }
                 }
             },
+            SearchWordOrTag::RawTag(ref t) if t.is_empty() => { //any tag, see match_param
+                item_refs.extend(self.items.iter().filter(|item| sp.keeps(item) && Self::match_param(item, &search, sp)));
+            },
             SearchWordOrTag::RawTag(subsequence) => {
                 for item in &self.items {
-                    if item.done {
+                    if !sp.keeps(item) {
                         continue
                     }
-                    for Tag(sequence) in &item.tags {
-                        if Self::match_subsequence(&sequence, &subsequence) {
+                    for tag in &item.tags {
+                        if matching::match_word(&tag.key(), &tag_key(&subsequence), sp.tag_mode(&subsequence), sp.tags_ignore_case) {
                             item_refs.push(item);
                             break
                         }
                     }
                 }
             },
+            SearchWordOrTag::RawAny(_) | SearchWordOrTag::WithMode(..) => {
+                item_refs.extend(self.items.iter().filter(|item| sp.keeps(item) && Self::match_param(item, &search, sp)));
+            },
         }
     }
-    fn search_filter(self: &TodoList2, refs: &mut Vec<&TodoItem>, search: SearchWordOrTag) {
+    fn search_filter(self: &TodoList2, refs: &mut Vec<&TodoItem>, search: SearchWordOrTag, sp: &SearchParams) {
         match search {
             SearchWordOrTag::RawWord(subsequence) => {
                 refs.retain(|item| {
-                    if item.done {
+                    if !sp.keeps(item) {
                         return false
                     }
                     for Word(sequence) in &item.description {
-                        if Self::match_subsequence(&sequence, &subsequence) {
+                        if matching::match_word(&sequence, &subsequence, sp.mode, sp.words_ignore_case) {
                             return true
                         }
                     }
                     false
                 })
             },
+            SearchWordOrTag::RawTag(ref t) if t.is_empty() => {
+                refs.retain(|item| sp.keeps(item) && Self::match_param(item, &search, sp))
+            },
             SearchWordOrTag::RawTag(subsequence) => {
                 refs.retain(|item| {
-                    if item.done {
+                    if !sp.keeps(item) {
                         return false
                     }
-                    for Tag(sequence) in &item.tags {
-                        if Self::match_subsequence(&sequence, &subsequence) {
+                    for tag in &item.tags {
+                        if matching::match_word(&tag.key(), &tag_key(&subsequence), sp.tag_mode(&subsequence), sp.tags_ignore_case) {
                             return true
                         }
                     }
                     false
                 })
             },
+            SearchWordOrTag::RawAny(_) | SearchWordOrTag::WithMode(..) => {
+                refs.retain(|item| sp.keeps(item) && Self::match_param(item, &search, sp))
+            },
         }
     }
-    fn match_subsequence(sequence: &str, subsequence: &str) -> bool {
-        let mut sub_index = 0;
-        let sub_bytes = subsequence.as_bytes(); //this only splits on exact characters when we're using ASCII, not unicode
-        for byte in sequence.as_bytes().iter() {
-            if sub_index == subsequence.len() {
-                return true
-            }
-            // if sequence.len() - seq_index < subsequence.len() - sub_index { //length checking added here (seems to make it slightly slower overall)
-            //     return false
-            // }
-            unsafe { //safe because termination is guaranteed before index gets too large
-                if byte == sub_bytes.get_unchecked(sub_index) {
-                    sub_index += 1;
-                }
-            }
-        }
-        sub_index == subsequence.len()
+    fn match_param(item: &TodoItem, param: &SearchWordOrTag, sp: &SearchParams) -> bool {
+        TodoList::match_param(item, param, sp)
     }
 }
 impl<'a> TodoLister for TodoList2 {
     fn push(&mut self, description: Vec<Word>, tags: Vec<Tag>) -> TodoItem {
-        let item = TodoItem::new(Index::new(self.items.len() as u64), description, tags, false);
+        let item = TodoItem::new(Index::new(self.top_index), description, tags, false);
         let item_c = item.clone();
         self.items.push(item);
+        self.top_index += 1;
         item_c
     }
     fn done_with_index(&mut self, idx: Index) -> Option<Index> {
         if let Ok(n) = self.items.binary_search_by_key(&idx, |item| item.index) {
+            if !self.items[n].done {
+                self.last_done = Some(idx);
+                self.completions += 1;
+                self.items[n].completed = Some(self.completions);
+            }
             self.items[n].done = true;
             Some(idx)
         }
@@ -324,23 +1034,60 @@ impl<'a> TodoLister for TodoList2 {
             None
         }
     }
-    fn search(&self, sp: SearchParams) -> Vec<&TodoItem> {
+    fn toggle_with_index(&mut self, idx: Index) -> Option<bool> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        self.items[n].done = !self.items[n].done;
+        if self.items[n].done {
+            self.last_done = Some(idx);
+            self.completions += 1;
+            self.items[n].completed = Some(self.completions);
+        }
+        else {
+            self.items[n].done_note = None;
+            self.items[n].completed = None;
+        }
+        Some(self.items[n].done)
+    }
+    fn undo_last_done(&mut self) -> Option<Index> {
+        let idx = self.last_done.take()?;
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        if !self.items[n].done {
+            return None
+        }
+        self.items[n].done = false;
+        self.items[n].done_note = None;
+        self.items[n].completed = None;
+        Some(idx)
+    }
+    fn search(&self, mut sp: SearchParams) -> Vec<&TodoItem> {
         //get item_refs
         let entry: &mut Option<Vec<usize>> = &mut self.item_refs.borrow_mut();
         let item_refs: Vec<usize> = mem::take(entry).unwrap();
         let mut item_refs: Vec<&TodoItem> = item_refs.into_iter().filter_map(|_| None).collect(); //should not cause a realloc
 
         //add and filter references
-        let mut params = sp.params.into_iter();
-        if let Some(first_param) = params.next() {
-            self.search_initial(&mut item_refs, first_param);
+        if (sp.combine == CombineMode::Any || sp.tag_combine.is_some()) && !sp.params.is_empty() { //checked per item, in item order
+            item_refs.extend(self.items.iter().filter(|item| sp.keeps(item) && sp.combines(|param| Self::match_param(item, param, &sp))));
+        }
+        else if !sp.params.is_empty() {
+            let mut params = mem::take(&mut sp.params).into_iter();
+            self.search_initial(&mut item_refs, params.next().unwrap(), &sp); //there's at least one
             for param in params {
-                self.search_filter(&mut item_refs, param);
+                self.search_filter(&mut item_refs, param, &sp);
             }
         }
+        else { //no search terms matches every item which isn't done, like TodoList
+            item_refs.extend(self.items.iter().filter(|item| sp.keeps(item)));
+        }
+
+        //the complement, replacing the matches in item_refs so its allocation is still reused
+        if sp.negate {
+            let matched: Vec<Index> = item_refs.drain(..).map(|item| item.index).collect();
+            item_refs.extend(self.items.iter().filter(|item| matched.binary_search(&item.index).is_err() && sp.keeps(item)));
+        }
 
         //save results
-        let results = item_refs.to_owned();
+        let results = sp.paginate(item_refs.iter().copied());
 
         //put item_refs back
         let item_refs: Vec<usize> = item_refs.into_iter().filter_map(|_| None).collect();
@@ -349,69 +1096,1678 @@ impl<'a> TodoLister for TodoList2 {
         //return results
         results
     }
+    fn retag_with_index(&mut self, idx: Index, old: Tag, new: Tag) -> Option<Index> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        if self.items[n].retag(&old, new) {
+            Some(idx)
+        }
+        else {
+            None
+        }
+    }
+    fn bump_priority(&mut self, idx: Index, delta: i32) -> Option<u8> {
+        Some(item_mut(&mut self.items, idx)?.bump_priority(delta))
+    }
+    fn clear_tags(&mut self, idx: Index) -> Option<usize> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        Some(mem::take(&mut self.items[n].tags).len())
+    }
+    fn edit(&mut self, idx: Index, description: Vec<Word>) -> Option<Vec<Word>> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        Some(mem::replace(&mut self.items[n].description, description))
+    }
+    fn set_note(&mut self, idx: Index, note: Option<String>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.note = note;
+        Some(idx)
+    }
+    fn set_done_note(&mut self, idx: Index, note: Option<String>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.set_done_note(note).then_some(idx)
+    }
+    fn set_due(&mut self, idx: Index, due: Option<Date>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.due = due;
+        Some(idx)
+    }
+    fn get(&self, idx: Index) -> Option<&TodoItem> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        Some(&self.items[n])
+    }
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+    fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + items_memory(&self.items)
+    }
+    fn next_index(&self) -> Index {
+        Index::new(self.top_index)
+    }
+    fn done_items(&self) -> Vec<&TodoItem> {
+        self.items.iter().filter(|item| item.done).collect()
+    }
+    fn page(&self, offset: usize, limit: usize) -> (Vec<&TodoItem>, usize) {
+        live_page(&self.items, offset, limit)
+    }
+    fn all_tags(&self) -> Vec<Tag> {
+        live_tags(&self.items)
+    }
+    fn retain<F: FnMut(&TodoItem) -> bool>(&mut self, f: F) {
+        self.items.retain(f);
+    }
+    fn reindex(&mut self) -> FxHashMap<Index, Index> {
+        let mapping = reindex_items(self.items.iter_mut());
+        self.top_index = self.items.len() as u64;
+        self.last_done = self.last_done.and_then(|idx| mapping.get(&idx).copied());
+        mapping
+    }
+    fn swap(&mut self, a: Index, b: Index) -> bool {
+        if !swap_items(&mut self.items, a, b) {
+            return false
+        }
+        self.last_done = self.last_done.map(|idx| swapped_index(idx, a, b));
+        true
+    }
+    fn reset(&mut self) {
+        self.items.clear();
+        self.top_index = 0;
+        self.last_done = None;
+        self.completions = 0;
+    }
 }
 
+//type-ahead search for a description word as it's typed, see TriedoList::start_session
+//a word matching the longer term also matches the shorter one, so each character typed only has to check the previous candidates
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchSession {
+    term: String,
+    candidates: Vec<u64>, //ascending ids of the items matching term
+}
+impl SearchSession {
+    pub fn term(&self) -> &str {
+        &self.term
+    }
+}
+
+//what happens to a TriedoList item when it's marked done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DonePolicy {
+    KeepInItems, //it stays in the items and the tries (marked done in them), so searches including done items can still use the tries
+    #[default]
+    RemoveFromTrie, //it stays in the items but not the tries, keeping them small (a search including done items scans instead)
+    MoveToArchive, //it leaves the items (and tries) for the archive, so searches don't find it but get still does
+}
+
+//a threshold and what to call with a word and its number of items when adding an item takes that past it
+type WordWarning = Option<(usize, fn(&str, usize))>;
+
+#[derive(Debug, Clone)]
 pub struct TriedoList<T: Trie + Default> {
     items: Vec<TodoItem>,
     words: T,
+    lower_words: T, //the same words ASCII lowercased, for searching them ignoring case
     tags: T,
+    top_index: u64,
+    last_done: Option<Index>,
+    completions: u64,
+    done_policy: DonePolicy,
+    archive: FxHashMap<Index, TodoItem>, //items done under MoveToArchive
+    word_warning: WordWarning, //see with_word_warning
+}
+//the word warning is left out, since it only reports on the list rather than being part of it (and fn pointers can't be compared reliably)
+impl<T: Trie + Default + PartialEq> PartialEq for TriedoList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items && self.words == other.words && self.lower_words == other.lower_words && self.tags == other.tags
+            && self.top_index == other.top_index && self.last_done == other.last_done && self.completions == other.completions
+            && self.done_policy == other.done_policy && self.archive == other.archive
+    }
 }
+impl<T: Trie + Default + Eq> Eq for TriedoList<T> {}
 impl<T: Trie + Default> TriedoList<T> {
     pub fn new() -> Self {
         TriedoList {
             items: Vec::new(),
             words: T::default(),
+            lower_words: T::default(),
             tags: T::default(),
+            top_index: 0,
+            last_done: None,
+            completions: 0,
+            done_policy: DonePolicy::default(),
+            archive: FxHashMap::default(),
+            word_warning: None,
         }
     }
-}
+    //room for n items before the list has to reallocate, the tries grow node by node so they can't be sized ahead
+    pub fn with_capacity(n: usize) -> Self {
+        TriedoList {
+            items: Vec::with_capacity(n),
+            ..Self::new()
+        }
+    }
+    pub fn reserve(&mut self, n: usize) {
+        self.items.reserve(n);
+    }
+    pub fn capacity(&self) -> usize {
+        self.items.capacity()
+    }
+    pub fn from_items(items: Vec<TodoItem>) -> Result<Self, QueryError> {
+        let top_index = next_index(&items)?;
+        let completions = last_completion(&items);
+        let mut tl = TriedoList {
+            items,
+            words: T::default(),
+            lower_words: T::default(),
+            tags: T::default(),
+            top_index,
+            last_done: None,
+            completions,
+            done_policy: DonePolicy::default(),
+            archive: FxHashMap::default(),
+            word_warning: None,
+        };
+        tl.rebuild_tries();
+        Ok(tl)
+    }
+    //applies to items already done as well as those done later (moving them to the archive for MoveToArchive)
+    pub fn with_done_policy(mut self, done_policy: DonePolicy) -> Self {
+        self.done_policy = done_policy;
+        if done_policy == DonePolicy::MoveToArchive {
+            let (done, items): (Vec<TodoItem>, Vec<TodoItem>) = mem::take(&mut self.items).into_iter().partition(|item| item.done);
+            self.items = items;
+            self.archive.extend(done.into_iter().map(|item| (item.index, item)));
+        }
+        self.rebuild_tries();
+        self
+    }
+    //calls on_large with a word and its number of items when adding or editing an item takes that past the threshold
+    //for spotting stop-words, which slow searches down, rebuilding the tries (like compact does) doesn't report them again
+    pub fn with_word_warning(mut self, threshold: usize, on_large: fn(&str, usize)) -> Self {
+        self.word_warning = Some((threshold, on_large));
+        self
+    }
+    //archived items in ascending index order
+    //searches and len leave them out, get, done_items and setting the done note still find them,
+    //and toggle and undo_last_done can bring them back
+    pub fn archive(&self) -> Vec<&TodoItem> {
+        let mut archive: Vec<&TodoItem> = self.archive.values().collect();
+        archive.sort_unstable_by_key(|item| item.index);
+        archive
+    }
+    fn keep_done(&self) -> bool {
+        self.done_policy == DonePolicy::KeepInItems
+    }
+    //marks the nth item done, under MoveToArchive it's moved to the archive
+    fn complete(&mut self, n: usize) {
+        let idx = self.items[n].index;
+        self.items[n].done = true;
+        self.completions += 1;
+        self.items[n].completed = Some(self.completions);
+        if self.keep_done() {
+            self.set_done_in_tries(idx, true);
+        }
+        else {
+            self.delete_from_tries(idx);
+        }
+        if self.done_policy == DonePolicy::MoveToArchive {
+            let item = self.items.remove(n);
+            self.archive.insert(idx, item);
+        }
+    }
+    //marks an item not done again, taking it back from the archive if it's there
+    fn reopen(&mut self, idx: Index) -> Option<()> {
+        let n = match self.items.binary_search_by_key(&idx, |item| item.index) {
+            Ok(n) => n,
+            Err(n) => {
+                self.items.insert(n, self.archive.remove(&idx)?);
+                n
+            },
+        };
+        if !self.items[n].done {
+            return None
+        }
+        self.items[n].done = false;
+        self.items[n].done_note = None;
+        self.items[n].completed = None;
+        if self.keep_done() {
+            self.set_done_in_tries(idx, false);
+        }
+        else {
+            Self::add_to_tries(&mut self.words, &mut self.lower_words, &mut self.tags, &self.items[n], self.word_warning);
+        }
+        Some(())
+    }
+    //words of items which aren't done (or of every item, when done items are kept in the tries), for which some prefix is within max_dist edits of the given prefix
+    pub fn complete_word_fuzzy(&self, prefix: &str, max_dist: usize, limit: usize) -> Vec<String> {
+        self.words.complete_word_fuzzy(prefix, max_dist, limit)
+    }
+    //number of different words in the descriptions of items which aren't done (or of every item, when done items are kept in the tries)
+    pub fn distinct_word_count(&self) -> usize {
+        self.words.word_count()
+    }
+    //those words in alphabetical order, each with the indices of the items which have it in ascending order
+    pub fn iter_words(&self) -> impl Iterator<Item = (String, Vec<Index>)> + '_ {
+        self.words.iter_terminals().map(|(word, ends)| {
+            let mut idxs: Vec<Index> = ends.keys().copied().map(Index::new).collect();
+            idxs.sort_unstable();
+            (word, idxs)
+        })
+    }
+    //a session with an empty term, which every item that isn't done matches
+    //the list shouldn't be changed while it's in use, or items added since won't be found
+    pub fn start_session(&self) -> SearchSession {
+        SearchSession {
+            term: String::new(),
+            candidates: self.items.iter().filter(|item| !item.done).map(|item| item.index.value()).collect(),
+        }
+    }
+    //appends c to the session's term, keeping the candidates which still match, returning how many there are
+    pub fn refine(&self, session: &mut SearchSession, c: char) -> usize {
+        session.term.push(c);
+        let previous: FxHashSet<u64> = session.candidates.iter().copied().collect();
+        let matches = self.words.search(vec![&session.term], Some(&previous)); //the filter prunes the trie, it doesn't limit the results
+        session.candidates.retain(|id| matches.contains(id));
+        session.candidates.len()
+    }
+    //the items matching the session's term, like a search for it
+    pub fn session_results(&self, session: &SearchSession) -> Vec<&TodoItem> {
+        session.candidates.iter()
+            .filter_map(|id| self.items.binary_search_by_key(&Index::new(*id), |item| item.index).ok())
+            .map(|n| &self.items[n])
+            .filter(|item| !item.done)
+            .collect()
+    }
+    //deleting from a trie only removes ids, leaving nodes behind for words no live item has any more
+    //this rebuilds the tries without them and releases spare item storage, indices are unchanged
+    pub fn compact(&mut self) {
+        self.rebuild_tries();
+        self.items.shrink_to_fit();
+    }
+    //refills both tries from the items alone, for when they may no longer agree (say after changing items through other code)
+    //searches only use the tries, so this is the way to recover, it doesn't report words past the word warning again
+    pub fn rebuild_indices(&mut self) {
+        self.rebuild_tries();
+    }
+    //a cheap upper bound on the number of results, from the trie lookups alone without intersecting them
+    //(the smallest term's candidates when every term has to match, all of them added up when any can)
+    pub fn estimate_cost(&self, sp: &SearchParams) -> usize {
+        if sp.params.is_empty() {
+            return self.items.iter().filter(|item| sp.keeps(item)).count()
+        }
+        if sp.include_done && !self.keep_done() { //the search will scan, as the tries are missing the done items
+            return self.items.len()
+        }
+        let sizes = sp.params.iter().map(|param| self.term_candidates(param, sp, None).len());
+        match sp.combine {
+            CombineMode::All => sizes.min().unwrap_or(0),
+            CombineMode::Any => sizes.sum(),
+        }
+    }
+    //ids of items (in ascending order) which subsequence match a single search term
+    //glob wildcards are left out, the term's other characters still appear in order in any word it globs
+    //the filter only prunes the tries' search (see Trie::search), ids outside it can still be returned
+    //terms ignoring case are lowercased and looked up in the lowercased words, tag terms are always folded like the tags' keys
+    fn term_candidates(&self, param: &SearchWordOrTag, sp: &SearchParams, filter: Option<&FxHashSet<u64>>) -> Vec<u64> {
+        let sorted = |trie: &T, s: &str, ignore_case: bool| {
+            let s = if ignore_case { s.to_ascii_lowercase() } else { s.to_string() };
+            let mut ids: Vec<u64> = trie.search_with_done(vec![&s], filter, sp.include_done).into_iter().collect();
+            ids.sort_unstable();
+            ids
+        };
+        let words = if sp.words_ignore_case { &self.lower_words } else { &self.words };
+        match param {
+            SearchWordOrTag::RawWord(w) => sorted(words, &w.replace(['*', '?'], ""), sp.words_ignore_case),
+            SearchWordOrTag::RawTag(t) => sorted(&self.tags, &tag_key(t), sp.tags_ignore_case),
+            SearchWordOrTag::RawAny(s) => {
+                let s = s.replace(['*', '?'], "");
+                union_sorted(&sorted(words, &s, sp.words_ignore_case), &sorted(&self.tags, &tag_key(&s), sp.tags_ignore_case))
+            },
+            SearchWordOrTag::WithMode(_, term) => self.term_candidates(term, sp, filter),
+        }
+    }
+    //makes an item searchable by adding its words and tags to the tries, a done item is marked done in them
+    fn add_to_tries(words: &mut T, lower_words: &mut T, tags: &mut T, item: &TodoItem, word_warning: WordWarning) {
+        Self::add_words(words, lower_words, item, word_warning);
+        Self::add_tags(tags, item);
+    }
+    fn add_tags(tags: &mut T, item: &TodoItem) {
+        let keys: Vec<_> = item.tags.iter().map(Tag::key).collect(); //tags are keyed lowercased
+        tags.add(item.index.value(), keys.iter().map(|t| &t[..]).collect());
+        if item.done {
+            tags.set_done(item.index.value(), true);
+        }
+    }
+    //the word warning only looks at the words as written, so a word isn't reported twice
+    fn add_words(words: &mut T, lower_words: &mut T, item: &TodoItem, word_warning: WordWarning) {
+        let description = item.description.iter().map(|Word(s)| &s[..]).collect();
+        match word_warning {
+            Some((threshold, mut on_large)) => words.add_checked(item.index.value(), description, threshold, &mut on_large),
+            None => words.add(item.index.value(), description),
+        }
+        let lowered: Vec<String> = item.description.iter().map(|Word(s)| s.to_ascii_lowercase()).collect();
+        lower_words.add(item.index.value(), lowered.iter().map(|w| &w[..]).collect());
+        if item.done {
+            words.set_done(item.index.value(), true);
+            lower_words.set_done(item.index.value(), true);
+        }
+    }
+    fn set_done_in_tries(&mut self, idx: Index, done: bool) {
+        self.words.set_done(idx.value(), done);
+        self.lower_words.set_done(idx.value(), done);
+        self.tags.set_done(idx.value(), done);
+    }
+    fn delete_from_tries(&mut self, idx: Index) {
+        self.words.delete(idx.value());
+        self.lower_words.delete(idx.value());
+        self.tags.delete(idx.value());
+    }
+    //replaces both tries with fresh ones containing only the items which aren't done (unless done items are kept in them)
+    fn rebuild_tries(&mut self) {
+        self.words.clear();
+        self.lower_words.clear();
+        self.tags.clear();
+        let keep_done = self.keep_done();
+        for item in self.items.iter().filter(|item| keep_done || !item.done) {
+            Self::add_to_tries(&mut self.words, &mut self.lower_words, &mut self.tags, item, None);
+        }
+    }
+}
 impl<T: Trie + Default> TodoLister for TriedoList<T> {
     fn push(&mut self, description: Vec<Word>, tags: Vec<Tag>) -> TodoItem {
-        self.words.add(self.items.len() as u64, description.iter().map(|Word(s)| &s[..]).collect());
-        self.tags.add(self.items.len() as u64, tags.iter().map(|Tag(t)| &t[..]).collect());
-        let item = TodoItem::new(Index::new(self.items.len() as u64), description, tags, false);
+        let item = TodoItem::new(Index::new(self.top_index), description, tags, false);
+        Self::add_to_tries(&mut self.words, &mut self.lower_words, &mut self.tags, &item, self.word_warning);
         let item_c = item.clone();
         self.items.push(item);
+        self.top_index += 1;
         item_c
     }
     fn done_with_index(&mut self, idx: Index) -> Option<Index> {
-        self.words.delete(idx.value());
-        if let Ok(n) = self.items.binary_search_by_key(&idx, |item| item.index) {
-            self.items[n].done = true;
-            Some(idx)
+        match self.items.binary_search_by_key(&idx, |item| item.index) {
+            Ok(n) if !self.items[n].done => {
+                self.last_done = Some(idx);
+                self.complete(n);
+                Some(idx)
+            },
+            Ok(_) => Some(idx),
+            Err(_) if self.archive.contains_key(&idx) => Some(idx), //done already
+            Err(_) => None,
         }
-        else {
-            None
+    }
+    fn toggle_with_index(&mut self, idx: Index) -> Option<bool> {
+        match self.items.binary_search_by_key(&idx, |item| item.index) {
+            Ok(n) if !self.items[n].done => {
+                self.last_done = Some(idx);
+                self.complete(n);
+                Some(true)
+            },
+            _ => self.reopen(idx).map(|_| false),
         }
     }
+    fn undo_last_done(&mut self) -> Option<Index> {
+        let idx = self.last_done.take()?;
+        self.reopen(idx).map(|_| idx)
+    }
     fn search(&self, sp: SearchParams) -> Vec<&TodoItem> {
-        let mut word_searches = Vec::new();
-        let mut tag_searches = Vec::new();
-        for param in &sp.params {
-            match param {
-                SearchWordOrTag::RawWord(w) => {
-                    word_searches.push(&w[..]);
-                },
-                SearchWordOrTag::RawTag(t) => {
-                    tag_searches.push(&t[..]);
-                },
+        let term_matches = |param: &SearchWordOrTag, filter: Option<&FxHashSet<u64>>| -> Vec<u64> {
+            //the tries can't be used when they're missing done items to include
+            if sp.include_done && !self.keep_done() {
+                return self.items.iter()
+                    .filter(|item| sp.keeps(item) && TodoList::match_param(item, param, &sp))
+                    .map(|item| item.index.value())
+                    .collect()
+            }
+            let candidates = self.term_candidates(param, &sp, filter);
+            let (term, own_mode) = param.split_mode();
+            let tags_subsequence = match term {
+                SearchWordOrTag::RawWord(_) => true,
+                SearchWordOrTag::RawTag(t) if t.is_empty() => true, //every id in the tags trie has some tag
+                SearchWordOrTag::RawTag(s) | SearchWordOrTag::RawAny(s) => sp.term_tag_mode(s, own_mode) == MatchMode::Subsequence,
+                SearchWordOrTag::WithMode(..) => false,
+            };
+            if own_mode.unwrap_or(sp.mode) == MatchMode::Subsequence && tags_subsequence {
+                return candidates
+            }
+            //the tries only match subsequences, so narrower modes (including exact tags) have to check each candidate
+            candidates.into_iter()
+                .filter(|index| matches!(self.items.binary_search_by_key(&Index::new(*index), |item| item.index),
+                    Ok(n) if TodoList::match_param(&self.items[n], param, &sp)))
+                .collect()
+        };
+        //when every term has to match, the ids matching the terms so far are passed on to prune the next term's trie search
+        let fold = |combine: CombineMode, params: Vec<&SearchWordOrTag>| {
+            let mut params = params.into_iter();
+            let mut acc = term_matches(params.next()?, None);
+            for param in params {
+                acc = match combine {
+                    CombineMode::All => {
+                        let filter: FxHashSet<u64> = acc.iter().copied().collect();
+                        intersect_sorted(&acc, &term_matches(param, Some(&filter)))
+                    },
+                    CombineMode::Any => union_sorted(&acc, &term_matches(param, None)),
+                };
             }
+            Some(acc)
+        };
+        let indices = match sp.tag_combine {
+            None => fold(sp.combine, sp.params.iter().collect()),
+            Some(tag_combine) => {
+                let (tag_params, word_params): (Vec<_>, Vec<_>) = sp.params.iter().partition(|param| SearchParams::is_tag_term(param));
+                match (fold(sp.combine, word_params), fold(tag_combine, tag_params)) {
+                    (Some(word_indices), Some(tag_indices)) => Some(intersect_sorted(&word_indices, &tag_indices)),
+                    (word_indices, tag_indices) => word_indices.or(tag_indices), //an empty group doesn't rule anything out
+                }
+            },
+        };
+        let indices = match indices {
+            Some(indices) => indices,
+            None if sp.negate => return Vec::new(),
+            None => return sp.paginate(self.items.iter().filter(|item| sp.keeps(item))), //no search terms matches every item which isn't done
+        };
+        if sp.negate { //every item not matched, whether or not the tries hold it
+            return sp.paginate(self.items.iter()
+                .filter(|item| indices.binary_search(&item.index.value()).is_err() && sp.keeps(item)))
         }
-        let indices;
-        if word_searches.len() > 0 && tag_searches.len() > 0 {
-            let word_indices = self.words.search(word_searches, None);
-            indices = word_indices.intersection(&self.tags.search(tag_searches, Some(&word_indices))).cloned().collect();
+        //items may have been removed with retain, so an index doesn't necessarily match its position
+        //the tries have left out done items already, unless the search includes them
+        sp.paginate(indices.iter()
+            .filter_map(|index| self.items.binary_search_by_key(&Index::new(*index), |item| item.index).ok())
+            .map(|n| &self.items[n])
+            .filter(|item| sp.keeps(item)))
+    }
+    fn retag_with_index(&mut self, idx: Index, old: Tag, new: Tag) -> Option<Index> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        if !self.items[n].retag(&old, new) {
+            return None
         }
-        else if word_searches.len() > 0 {
-            indices = self.words.search(word_searches, None);
+        if self.keep_done() || !self.items[n].done {
+            //tries can't remove an id from just one word, so remove it from every tag and add the remaining tags back
+            self.tags.delete(idx.value());
+            Self::add_tags(&mut self.tags, &self.items[n]);
         }
-        else if tag_searches.len() > 0 {
-            indices = self.tags.search(tag_searches, None);
+        Some(idx)
+    }
+    fn bump_priority(&mut self, idx: Index, delta: i32) -> Option<u8> {
+        Some(item_mut(&mut self.items, idx)?.bump_priority(delta))
+    }
+    fn clear_tags(&mut self, idx: Index) -> Option<usize> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        self.tags.delete(idx.value()); //a no-op for a done item the tries don't have
+        Some(mem::take(&mut self.items[n].tags).len())
+    }
+    fn edit(&mut self, idx: Index, description: Vec<Word>) -> Option<Vec<Word>> {
+        let n = self.items.binary_search_by_key(&idx, |item| item.index).ok()?;
+        let old = mem::replace(&mut self.items[n].description, description);
+        if self.keep_done() || !self.items[n].done {
+            //like retag, every word is removed and the new ones added
+            self.words.delete(idx.value());
+            self.lower_words.delete(idx.value());
+            Self::add_words(&mut self.words, &mut self.lower_words, &self.items[n], self.word_warning);
         }
-        else {
-            return Vec::new() 
+        Some(old)
+    }
+    fn set_note(&mut self, idx: Index, note: Option<String>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.note = note;
+        Some(idx)
+    }
+    //archived items are done, so their note can be set too
+    fn set_done_note(&mut self, idx: Index, note: Option<String>) -> Option<Index> {
+        let item = match self.items.binary_search_by_key(&idx, |item| item.index) {
+            Ok(n) => &mut self.items[n],
+            Err(_) => self.archive.get_mut(&idx)?,
+        };
+        item.set_done_note(note).then_some(idx)
+    }
+    fn set_due(&mut self, idx: Index, due: Option<Date>) -> Option<Index> {
+        item_mut(&mut self.items, idx)?.due = due;
+        Some(idx)
+    }
+    //archived items are found too
+    fn get(&self, idx: Index) -> Option<&TodoItem> {
+        match self.items.binary_search_by_key(&idx, |item| item.index) {
+            Ok(n) => Some(&self.items[n]),
+            Err(_) => self.archive.get(&idx),
+        }
+    }
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+    //the tries are estimated by their node count, without the ids stored in each node
+    fn memory_usage(&self) -> usize {
+        mem::size_of::<Self>() + items_memory(&self.items) + items_memory(self.archive.values())
+            + (self.words.node_count() + self.lower_words.node_count() + self.tags.node_count()) * mem::size_of::<T>()
+    }
+    fn next_index(&self) -> Index {
+        Index::new(self.top_index)
+    }
+    fn done_items(&self) -> Vec<&TodoItem> {
+        //the tries can't list the done ids on their own, so scan for them
+        let mut done: Vec<&TodoItem> = self.items.iter().filter(|item| item.done).chain(self.archive.values()).collect();
+        if !self.archive.is_empty() {
+            done.sort_unstable_by_key(|item| item.index);
+        }
+        done
+    }
+    fn page(&self, offset: usize, limit: usize) -> (Vec<&TodoItem>, usize) {
+        live_page(&self.items, offset, limit)
+    }
+    fn all_tags(&self) -> Vec<Tag> {
+        live_tags(&self.items) //the tags trie only has the lowercased keys (and has done items under KeepInItems), so the items are used
+    }
+    //every item which isn't done, less those the tags trie finds with the tag
+    fn items_without_tag(&self, tag: &Tag) -> Vec<&TodoItem> {
+        let with_tag: Vec<u64> = self.tags.search_sorted(&tag.key()).into_iter()
+            .filter(|id| matches!(self.get(Index::new(*id)), Some(item) if item.has_tag(tag))) //the trie matches subsequences
+            .collect();
+        self.items.iter()
+            .filter(|item| !item.done && with_tag.binary_search(&item.index.value()).is_err())
+            .collect()
+    }
+    fn retain<F: FnMut(&TodoItem) -> bool>(&mut self, f: F) {
+        self.items.retain(f);
+        self.rebuild_tries();
+    }
+    //archived items are split too, each half builds its own tries rather than cloning and pruning them
+    fn partition<F: Fn(&TodoItem) -> bool>(self, f: F) -> (Self, Self) {
+        let TriedoList { items, top_index, last_done, completions, done_policy, archive, word_warning, .. } = self;
+        let (items, rest_items): (Vec<TodoItem>, Vec<TodoItem>) = items.into_iter().partition(|item| f(item));
+        let (archive, rest_archive): (FxHashMap<Index, TodoItem>, FxHashMap<Index, TodoItem>) = archive.into_iter().partition(|(_, item)| f(item));
+        let part = |items, archive| {
+            let mut tl = TriedoList {
+                items,
+                words: T::default(),
+                lower_words: T::default(),
+                tags: T::default(),
+                top_index,
+                last_done,
+                completions,
+                done_policy,
+                archive,
+                word_warning,
+            };
+            tl.rebuild_tries();
+            tl
+        };
+        (part(items, archive), part(rest_items, rest_archive))
+    }
+    //archived items are renumbered along with the rest, in the same order, so they can still be put back in place
+    fn reindex(&mut self) -> FxHashMap<Index, Index> {
+        let mut all: Vec<&mut TodoItem> = self.items.iter_mut().chain(self.archive.values_mut()).collect();
+        all.sort_by_key(|item| item.index);
+        let mapping = reindex_items(all.into_iter());
+        self.archive = mem::take(&mut self.archive).into_values().map(|item| (item.index, item)).collect();
+        self.top_index = (self.items.len() + self.archive.len()) as u64;
+        self.last_done = self.last_done.and_then(|idx| mapping.get(&idx).copied());
+        self.rebuild_tries();
+        mapping
+    }
+    //archived items can't be swapped, each trie id is moved to the other item by removing both and adding them back
+    fn swap(&mut self, a: Index, b: Index) -> bool {
+        if !swap_items(&mut self.items, a, b) {
+            return false
+        }
+        self.last_done = self.last_done.map(|idx| swapped_index(idx, a, b));
+        for idx in [a, b] {
+            self.delete_from_tries(idx);
+        }
+        for idx in [a, b] {
+            let n = self.items.binary_search_by_key(&idx, |item| item.index).unwrap(); //both were just found
+            if !self.items[n].done || self.keep_done() {
+                Self::add_to_tries(&mut self.words, &mut self.lower_words, &mut self.tags, &self.items[n], self.word_warning);
+            }
+        }
+        true
+    }
+    fn reset(&mut self) {
+        self.items.clear();
+        self.archive.clear();
+        self.words.clear();
+        self.lower_words.clear();
+        self.tags.clear();
+        self.top_index = 0;
+        self.last_done = None;
+        self.completions = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_rejects_whitespace() {
+        assert_eq!(Word::new("two words"), None);
+        assert_eq!(Word::new("tab\tbed"), None);
+        assert_eq!(Word::new(""), None);
+        let description = vec![Word::new("buy").unwrap(), Word::new("milk").unwrap()];
+        let shown = Words { arr: &description, limit: None }.to_string();
+        let parsed: Vec<Word> = shown.split(' ').map(|w| Word::new(w).unwrap()).collect();
+        assert_eq!(parsed, description);
+    }
+
+    //runs a generic check against every list implementation
+    macro_rules! for_each_list {
+        ($check:ident) => {
+            $check(TodoList::new());
+            $check(TodoList2::new());
+            $check(TriedoList::<Trie1>::new());
+            $check(TriedoList::<Trie2>::new());
+            $check(TriedoList::<Trie3>::new());
+            $check(TriedoList::<Trie4>::new());
+            $check(TriedoList::<CharTrie>::new());
+            $check(TriedoList::<RadixTrie>::new());
+        };
+    }
+
+    fn words(s: &str) -> Vec<Word> {
+        s.split(' ').map(|w| Word::new(w).unwrap()).collect()
+    }
+
+    fn indices(items: Vec<&TodoItem>) -> Vec<u64> {
+        items.into_iter().map(|item| item.index.value()).collect()
+    }
+
+    fn search_words<T: TodoLister>(tl: &T, terms: &str) -> Vec<u64> {
+        let params = terms.split(' ').map(|t| SearchWordOrTag::RawWord(t.to_string())).collect();
+        indices(tl.search(SearchParams::new(params)))
+    }
+
+    #[test]
+    fn retain_prunes_searches() {
+        fn check<T: TodoLister>(mut tl: T) {
+            for _ in 0..6 {
+                tl.push(words("water plants"), Vec::new());
+            }
+            tl.retain(|item| item.index.value() % 2 == 0);
+            assert_eq!(tl.len(), 3);
+            assert_eq!(search_words(&tl, "water"), vec![0, 2, 4]);
+            assert_eq!(tl.push(words("water"), Vec::new()).index, Index::new(6)); //indices aren't reused
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn multi_term_search_agrees_with_scan() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water the plants"), Vec::new());
+            tl.push(words("wash the car"), Vec::new());
+            tl.push(words("walk the dog"), Vec::new());
+            tl.push(words("write a report"), Vec::new());
+            let mut naive = TodoList::new();
+            for item in tl.search(SearchParams::new(Vec::new())) {
+                naive.push(item.description.clone(), item.tags.clone());
+            }
+            for terms in ["wa th", "the r", "w a t e", "th dg", "r", "z w"] {
+                assert_eq!(search_words(&tl, terms), search_words(&naive, terms), "searching for {:?}", terms);
+            }
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn toggle_twice() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Vec::new());
+            assert_eq!(tl.toggle_with_index(Index::new(0)), Some(true));
+            assert!(search_words(&tl, "water").is_empty());
+            assert_eq!(tl.toggle_with_index(Index::new(0)), Some(false));
+            assert_eq!(search_words(&tl, "water"), vec![0]);
+            assert_eq!(tl.toggle_with_index(Index::new(1)), None);
         }
-        indices.iter().map(|index| &self.items[*index as usize]).collect()
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn empty_search_matches_live_items() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Vec::new());
+            tl.push(words("wash car"), Vec::new());
+            tl.push(words("walk dog"), Vec::new());
+            tl.done_with_index(Index::new(1));
+            assert_eq!(indices(tl.search(SearchParams::new(Vec::new()))), vec![0, 2]);
+        }
+        for_each_list!(check);
+    }
+
+    fn search_tag<T: TodoLister>(tl: &T, tag: &str) -> Vec<u64> {
+        indices(tl.search(SearchParams::new(vec![SearchWordOrTag::RawTag(tag.to_string())])))
+    }
+
+    #[test]
+    fn retag_single_item() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash car"), Tag::from_strings(["home"]));
+            assert_eq!(tl.retag_with_index(Index::new(0), Tag::new("home"), Tag::new("garden")), Some(Index::new(0)));
+            assert_eq!(search_tag(&tl, "home"), vec![1]);
+            assert_eq!(search_tag(&tl, "garden"), vec![0]);
+            assert_eq!(tl.retag_with_index(Index::new(0), Tag::new("home"), Tag::new("garden")), None);
+            //renaming to a tag the item already has leaves it once
+            assert_eq!(tl.retag_with_index(Index::new(0), Tag::new("weekly"), Tag::new("garden")), Some(Index::new(0)));
+            assert_eq!(tl.get(Index::new(0)).unwrap().tags, Tag::from_strings(["garden"]));
+            assert_eq!(search_tag(&tl, "weekly"), Vec::<u64>::new());
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn all_tags_of_live_items() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash car"), Tag::from_strings(["car", "home"]));
+            tl.push(words("file taxes"), Tag::from_strings(["admin", "home"]));
+            tl.done_with_index(Index::new(2));
+            assert_eq!(tl.all_tags(), Tag::from_strings(["car", "home", "weekly"]));
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn tags_from_strings() {
+        let expected = vec![Tag::new("work"), Tag::new("home"), Tag::new("work")];
+        assert_eq!(Tag::from_strings(vec!["work", "home", "work"]), expected);
+        assert_eq!(Tag::from_strings(["work", "home", "work"]), expected);
+        assert_eq!(Tag::from_strings("work home work".split(' ')), expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn any_field_terms() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["garden"]));
+            tl.push(words("buy garden chairs"), Tag::from_strings(["shop"]));
+            tl.push(words("wash car"), Vec::new());
+            let any = |s: &str| SearchParams::new(vec![SearchWordOrTag::RawAny(s.to_string())]);
+            assert_eq!(indices(tl.search(any("shop"))), vec![1]); //only a tag
+            assert_eq!(indices(tl.search(any("wash"))), vec![2]); //only a word
+            assert_eq!(indices(tl.search(any("garden"))), vec![0, 1]); //a tag of one, a word of the other
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn compact_keeps_indices() {
+        let mut tl = TriedoList::<Trie3>::new();
+        for description in ["water plants", "wash car", "walk dog", "write report", "weed garden"] {
+            tl.push(words(description), Tag::from_strings(["home"]));
+        }
+        tl.done_with_index(Index::new(1));
+        tl.done_with_index(Index::new(3));
+        let nodes = tl.words.node_count();
+        tl.compact();
+        assert!(tl.words.node_count() < nodes);
+        assert_eq!(search_words(&tl, "w"), vec![0, 2, 4]);
+        assert_eq!(search_tag(&tl, "home"), vec![0, 2, 4]);
+        assert_eq!(search_words(&tl, "dog"), vec![2]);
+        assert_eq!(tl.get(Index::new(4)).unwrap().description, words("weed garden"));
+    }
+
+    #[test]
+    fn read_only_view() {
+        //only needs a view, so it can't change the list
+        fn first_match<'a, L: TodoLister>(view: &TodoView<'a, L>, term: &str) -> Option<&'a TodoItem> {
+            view.search(SearchParams::new(vec![SearchWordOrTag::RawWord(term.to_string())])).into_iter().next()
+        }
+        let mut tl = TriedoList::<Trie4>::new();
+        tl.push(words("water plants"), Vec::new());
+        tl.push(words("wash car"), Vec::new());
+        let view = tl.view();
+        assert_eq!(first_match(&view, "car").map(|item| item.index), Some(Index::new(1)));
+        assert_eq!(view.get(Index::new(0)).unwrap().description, words("water plants"));
+        assert_eq!(view.len(), 2);
+        assert!(!view.is_empty());
+    }
+
+    #[test]
+    fn tags_with_spaces() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("review pr"), Tag::from_strings(["in progress"]));
+            tl.push(words("plan sprint"), Tag::from_strings(["progress"]));
+            assert_eq!(search_tag(&tl, "in progress"), vec![0]);
+            assert_eq!(search_tag(&tl, "progress"), vec![1]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn from_items_checks_indices() {
+        let item = |i, description| TodoItem::new(Index::new(i), words(description), Vec::new(), false);
+        let tl = TriedoList::<Trie4>::from_items(vec![item(2, "water plants"), item(5, "wash car")]).unwrap();
+        assert_eq!(tl.next_index(), Index::new(6));
+        assert_eq!(search_words(&tl, "car"), vec![5]);
+        let tl = TodoList::from_items(vec![item(0, "water plants"), item(1, "wash car")]).unwrap();
+        assert_eq!(tl.next_index(), Index::new(2));
+        assert!(TodoList::from_items(vec![item(1, "water plants"), item(1, "wash car")]).is_err());
+        assert!(TodoList2::from_items(vec![item(3, "water plants"), item(1, "wash car")]).is_err());
+        assert!(TriedoList::<Trie1>::from_items(vec![item(1, "water plants"), item(1, "wash car")]).is_err());
+    }
+
+    #[test]
+    fn max_gap_search() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("concatenate"), Vec::new());
+            tl.push(words("cart"), Vec::new());
+            tl.push(words("cabinet"), Vec::new());
+            let sp = |k| SearchParams::new(vec![SearchWordOrTag::RawWord(String::from("cat"))]).with_max_gap(k);
+            assert_eq!(indices(tl.search(sp(0))), vec![0]);
+            assert_eq!(indices(tl.search(sp(2))), vec![0, 1]);
+            assert_eq!(search_words(&tl, "cat"), vec![0, 1, 2]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn each_result_once() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("buy milk buy bread buy eggs"), Tag::from_strings(["shop", "shopping"]));
+            tl.push(words("walk dog"), Vec::new());
+            assert_eq!(search_words(&tl, "buy"), vec![0]);
+            assert_eq!(search_words(&tl, "b"), vec![0]);
+            assert_eq!(search_tag(&tl, ""), vec![0]);
+            let any = SearchParams::new(vec![SearchWordOrTag::RawWord(String::from("buy")), SearchWordOrTag::RawWord(String::from("milk"))]);
+            assert_eq!(indices(tl.search(any.with_combine(CombineMode::Any))), vec![0]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn estimate_cost_selective_and_broad() {
+        let mut tl = TriedoList::<Trie4>::new();
+        for n in 0..20 {
+            tl.push(words(if n == 7 { "water rare orchid" } else { "water plants" }), Vec::new());
+        }
+        let sp = |terms: &[&str]| SearchParams::new(terms.iter().map(|t| SearchWordOrTag::RawWord(t.to_string())).collect());
+        assert_eq!(tl.estimate_cost(&sp(&["orchid"])), 1);
+        assert_eq!(tl.estimate_cost(&sp(&["water"])), 20);
+        assert_eq!(tl.estimate_cost(&sp(&["water", "orchid"])), 1); //the smallest term's
+        assert_eq!(tl.estimate_cost(&sp(&["water", "orchid"]).with_combine(CombineMode::Any)), 21); //added up
+    }
+
+    #[test]
+    fn search_nth_in_and_out_of_range() {
+        fn check<T: TodoLister>(mut tl: T) {
+            for description in ["water plants", "wash car", "walk dog", "write report"] {
+                tl.push(words(description), Vec::new());
+            }
+            tl.done_with_index(Index::new(1));
+            let sp = || SearchParams::new(vec![SearchWordOrTag::RawWord("w".to_string())]);
+            assert_eq!(tl.search_nth(sp(), 0).map(|item| item.index), Some(Index::new(0)));
+            assert_eq!(tl.search_nth(sp(), 2).map(|item| item.index), Some(Index::new(3)));
+            assert_eq!(tl.search_nth(sp(), 3), None);
+            assert_eq!(tl.search_nth(sp().with_offset(1), 1).map(|item| item.index), Some(Index::new(3)));
+            assert_eq!(tl.search_nth(sp().with_limit(2), 2), None);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn undo_last_done_reverses_latest() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Vec::new());
+            tl.push(words("wash car"), Vec::new());
+            tl.done_with_index(Index::new(0));
+            tl.done_with_index(Index::new(1));
+            assert_eq!(tl.undo_last_done(), Some(Index::new(1)));
+            assert_eq!(search_words(&tl, "wa"), vec![1]);
+            assert!(tl.get(Index::new(0)).map_or(true, |item| item.done));
+            assert_eq!(tl.undo_last_done(), None); //only the latest one
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn builder_defaults() {
+        let item = TodoItemBuilder::new().description(words("water plants")).build(Index::new(3));
+        assert_eq!(item.index, Index::new(3));
+        assert_eq!(item.description, words("water plants"));
+        assert!(item.tags.is_empty());
+        assert!(!item.done);
+        assert_eq!(item.note, None);
+        assert_eq!(item.priority, 0);
+        assert_eq!(item.due, None);
+        assert_eq!(item.done_note, None);
+        assert_eq!(item.completed, None);
+    }
+
+    #[test]
+    fn ignore_case_for_words_and_tags() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["Home"]));
+            tl.push(words("wash car"), Tag::from_strings(["garden"]));
+            let search = |tl: &T, term: SearchWordOrTag, words_ic: bool, tags_ic: bool| {
+                indices(tl.search(SearchParams::new(vec![term]).with_words_ignore_case(words_ic).with_tags_ignore_case(tags_ic)))
+            };
+            let word = |w: &str| SearchWordOrTag::RawWord(w.to_string());
+            let tag = |t: &str| SearchWordOrTag::RawTag(t.to_string());
+            for (words_ic, tags_ic) in [(false, false), (true, false), (false, true), (true, true)] {
+                let found = |found: bool| if found { vec![0] } else { Vec::new() };
+                assert_eq!(search(&tl, word("water"), words_ic, tags_ic), vec![0]);
+                assert_eq!(search(&tl, word("Water"), words_ic, tags_ic), found(words_ic));
+                assert_eq!(search(&tl, word("WP"), words_ic, tags_ic), Vec::<u64>::new());
+                assert_eq!(search(&tl, SearchWordOrTag::WithMode(MatchMode::Exact, Box::new(word("PLANTS"))), words_ic, tags_ic), found(words_ic));
+                assert_eq!(search(&tl, tag("home"), words_ic, tags_ic), vec![0]); //tags are keyed lowercased
+                assert_eq!(search(&tl, tag("HoMe"), words_ic, tags_ic), vec![0]); //and so are the terms for them
+            }
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn loaded_words_and_tags_are_checked() {
+        let item = |description: &str, tag: &str| TodoItem::new(Index::new(0), vec![Word(description.to_string())], vec![Tag(tag.to_string())], false);
+        let good = serde_json::to_string(&item("water", "home office")).unwrap();
+        assert_eq!(serde_json::from_str::<TodoItem>(&good).unwrap(), item("water", "home office"));
+        for bad in [item("two words", "home"), item("", "home"), item("water", " "), item("water", "say \"hi\"")] {
+            assert!(serde_json::from_str::<TodoItem>(&serde_json::to_string(&bad).unwrap()).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn binary_round_trip() {
+        let mut tl = TodoList::new();
+        tl.push(words("water the plants"), Tag::from_strings(["home", "Weekly"]));
+        tl.push(words("wash car"), Vec::new());
+        tl.push(words("walk dog"), Tag::from_strings(["home"]));
+        tl.done_with_index(Index::new(1));
+        let bytes = tl.save_bin();
+        assert_eq!(TodoList::load_bin(&bytes).unwrap(), tl);
+        assert!(bytes.len() < serde_json::to_vec(&tl.items).unwrap().len());
+        let bad = TodoList::from_items(vec![TodoItem::new(Index::new(0), vec![Word("two words".to_string())], Vec::new(), false)]).unwrap();
+        assert!(TodoList::load_bin(&bad.save_bin()).is_err());
+    }
+
+    #[test]
+    fn tags_match_exactly_by_default() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("plan sprint"), Tag::from_strings(["proj"]));
+            tl.push(words("write spec"), Tag::from_strings(["project"]));
+            let search = |tl: &T, tag_policy: TagPolicy| {
+                indices(tl.search(SearchParams::new(vec![SearchWordOrTag::RawTag("proj".to_string())]).with_tag_policy(tag_policy)))
+            };
+            assert_eq!(search(&tl, TagPolicy::default()), vec![0]);
+            assert_eq!(search(&tl, TagPolicy::Exact), vec![0]);
+            assert_eq!(search(&tl, TagPolicy::LikeWords), vec![0, 1]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn search_owned_outlives_list() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            tl.push(words("wash car"), Vec::new());
+            let sp = || SearchParams::new(vec![SearchWordOrTag::RawWord("wa".to_string())]);
+            let borrowed: Vec<TodoItem> = tl.search(sp()).into_iter().cloned().collect();
+            let owned = tl.search_owned(sp());
+            drop(tl);
+            assert_eq!(owned, borrowed);
+            assert_eq!(owned.len(), 2);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn empty_tag_means_any_tag() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            tl.push(words("wash car"), Vec::new());
+            tl.push(words("walk dog"), Tag::from_strings(["pets", "daily"]));
+            assert_eq!(search_tag(&tl, ""), vec![0, 2]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn edit_returns_old_description() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Vec::new());
+            assert_eq!(tl.edit(Index::new(0), words("water the plants")), Some(words("water plants")));
+            assert_eq!(tl.edit(Index::new(0), words("water plants")), Some(words("water the plants")));
+            assert_eq!(search_words(&tl, "the"), Vec::<u64>::new());
+            assert_eq!(tl.edit(Index::new(1), words("wash car")), None);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn get_many_in_order() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Vec::new());
+            tl.push(words("wash car"), Vec::new());
+            tl.push(words("walk dog"), Vec::new());
+            tl.done_with_index(Index::new(1));
+            let found: Vec<Option<u64>> = tl.get_many(&[Index::new(2), Index::new(7), Index::new(1), Index::new(0), Index::new(2)])
+                .into_iter().map(|item| item.map(|item| item.index.value())).collect();
+            assert_eq!(found, vec![Some(2), None, None, Some(0), Some(2)]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn ties_break_by_ascending_index() {
+        fn check<T: TodoLister>(mut tl: T) {
+            for description in ["wash car", "water the plants", "walk dog", "write a report", "wax"] {
+                tl.push(words(description), Vec::new());
+            }
+            let sp = |order| SearchParams::new(vec![SearchWordOrTag::RawWord("w".to_string())]).with_order(order);
+            assert_eq!(indices(tl.search(sp(ResultOrder::Length))), vec![4, 0, 2, 1, 3]);
+            assert_eq!(indices(tl.search(sp(ResultOrder::LengthDescending))), vec![1, 3, 0, 2, 4]);
+        }
+        for_each_list!(check);
+        //sorting by priority the way Ord's comment says
+        let mut items: Vec<TodoItem> = [(3, 1), (0, 2), (2, 1), (1, 2), (4, 0)].iter()
+            .map(|&(i, priority)| TodoItemBuilder::new().priority(priority).build(Index::new(i)))
+            .collect();
+        items.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.cmp(b)));
+        assert_eq!(items.iter().map(|item| item.index.value()).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn glob_search() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("deploy site"), Vec::new());
+            tl.push(words("deprecate api"), Vec::new());
+            tl.push(words("redeploy site"), Vec::new());
+            tl.push(words("display dates"), Vec::new());
+            let glob = |tl: &T, pattern: &str| indices(tl.search(SearchParams::new(vec![SearchWordOrTag::RawWord(pattern.to_string())]).with_mode(MatchMode::Glob)));
+            assert_eq!(glob(&tl, "dep*"), vec![0, 1]);
+            assert_eq!(glob(&tl, "d?ploy"), vec![0]);
+            assert_eq!(glob(&tl, "*deploy"), vec![0, 2]);
+            assert_eq!(glob(&tl, "d*s"), vec![3]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn distinct_word_count_of_live_items() {
+        fn check<T: Trie + Default>(mut tl: TriedoList<T>) {
+            tl.push(words("water plants"), Vec::new());
+            tl.push(words("water the lawn"), Vec::new());
+            tl.push(words("wash the car"), Vec::new());
+            assert_eq!(tl.distinct_word_count(), 6);
+            tl.done_with_index(Index::new(2));
+            assert_eq!(tl.distinct_word_count(), 4); //the still belongs to the lawn
+            tl.done_with_index(Index::new(0));
+            assert_eq!(tl.distinct_word_count(), 3);
+        }
+        check(TriedoList::<Trie1>::new());
+        check(TriedoList::<Trie4>::new());
+        check(TriedoList::<CharTrie>::new());
+        check(TriedoList::<RadixTrie>::new());
+    }
+
+    #[test]
+    fn capacity_is_reserved() {
+        macro_rules! check {
+            ($list:ty) => {
+                let mut tl = <$list>::with_capacity(10);
+                let capacity = tl.capacity();
+                assert!(capacity >= 10);
+                for _ in 0..10 {
+                    tl.push(words("water plants"), Vec::new());
+                }
+                assert_eq!(tl.capacity(), capacity); //no reallocation
+                tl.reserve(20);
+                assert!(tl.capacity() >= 30);
+            };
+        }
+        check!(TodoList);
+        check!(TodoList2);
+        check!(TriedoList<Trie4>);
+        check!(TriedoList<RadixTrie>);
+    }
+
+    #[test]
+    fn done_items_marked_in_tries() {
+        fn check<T: Trie + Default>(tl: TriedoList<T>) {
+            let mut tl = tl.with_done_policy(DonePolicy::KeepInItems);
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            tl.push(words("wash car"), Tag::from_strings(["home"]));
+            tl.push(words("walk dog"), Vec::new());
+            tl.done_with_index(Index::new(1));
+            assert_eq!(tl.words.search_sorted("wa"), vec![0, 2]); //skipped by the trie itself
+            let search = |tl: &TriedoList<T>, include_done: bool, term: SearchWordOrTag| {
+                indices(tl.search(SearchParams::new(vec![term]).with_include_done(include_done)))
+            };
+            let wa = || SearchWordOrTag::RawWord("wa".to_string());
+            let home = || SearchWordOrTag::RawTag("home".to_string());
+            assert_eq!(search(&tl, false, wa()), vec![0, 2]);
+            assert_eq!(search(&tl, true, wa()), vec![0, 1, 2]);
+            assert_eq!(search(&tl, false, home()), vec![0]);
+            assert_eq!(search(&tl, true, home()), vec![0, 1]);
+            //changing a done item keeps it done in the tries
+            tl.edit(Index::new(1), words("wash the car"));
+            tl.retag_with_index(Index::new(1), Tag::new("home"), Tag::new("car"));
+            assert_eq!(search(&tl, false, wa()), vec![0, 2]);
+            assert_eq!(search(&tl, true, SearchWordOrTag::RawTag("car".to_string())), vec![1]);
+            assert_eq!(search(&tl, false, SearchWordOrTag::RawTag("car".to_string())), Vec::<u64>::new());
+            tl.toggle_with_index(Index::new(1));
+            assert_eq!(search(&tl, false, wa()), vec![0, 1, 2]);
+            tl.compact(); //rebuilt tries mark done items too
+            tl.done_with_index(Index::new(0));
+            tl.compact();
+            assert_eq!(search(&tl, false, wa()), vec![1, 2]);
+            assert_eq!(search(&tl, true, wa()), vec![0, 1, 2]);
+        }
+        check(TriedoList::<Trie1>::new());
+        check(TriedoList::<Trie2>::new());
+        check(TriedoList::<Trie3>::new());
+        check(TriedoList::<Trie4>::new());
+        check(TriedoList::<CharTrie>::new());
+        check(TriedoList::<RadixTrie>::new());
+    }
+
+    #[test]
+    fn diff_categorizes_changes() {
+        let mut before = TodoList::new();
+        for description in ["water plants", "wash car", "walk dog", "write report", "wax floor"] {
+            before.push(words(description), Vec::new());
+        }
+        before.done_with_index(Index::new(4));
+        let mut after = before.clone();
+        assert!(before.diff(&after).is_empty());
+        after.retain(|item| item.index != Index::new(1));
+        after.done_with_index(Index::new(2));
+        after.edit(Index::new(3), words("write the report"));
+        after.toggle_with_index(Index::new(4)); //not done again counts as an edit
+        after.push(words("wipe table"), Vec::new());
+        assert_eq!(before.diff(&after), ListDiff {
+            added: vec![Index::new(5)],
+            removed: vec![Index::new(1)],
+            completed: vec![Index::new(2)],
+            edited: vec![Index::new(3), Index::new(4)],
+        });
+    }
+
+    #[test]
+    fn tag_path_prefix() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("ship release"), Tag::from_strings(["work/project/task"]));
+            tl.push(words("book review"), Tag::from_strings(["work/admin"]));
+            tl.push(words("buy milk"), Tag::from_strings(["workshop"]));
+            tl.push(words("call mum"), Tag::from_strings(["home/work"]));
+            assert_eq!(search_tag(&tl, "work/"), vec![0, 1]);
+            assert_eq!(search_tag(&tl, "work/project/"), vec![0]);
+            assert_eq!(search_tag(&tl, "work"), Vec::<u64>::new()); //exact without the /
+            assert_eq!(search_tag(&tl, "project/"), Vec::<u64>::new());
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn longest_description_lowest_index_on_ties() {
+        fn check<T: TodoLister>(mut tl: T) {
+            assert_eq!(tl.longest_description(), None);
+            tl.push(words("water plants"), Vec::new());
+            tl.push(words("write the weekly report"), Vec::new());
+            tl.push(words("walk the dog today"), Vec::new());
+            tl.push(words("wash the car this week"), Vec::new());
+            assert_eq!(tl.longest_description().map(|item| item.index), Some(Index::new(3)));
+            tl.push(words("wrap the presents for the party"), Vec::new());
+            tl.done_with_index(Index::new(4)); //done items don't count
+            tl.push(words("wipe down the kitchen table"), Vec::new());
+            assert_eq!(tl.longest_description().map(|item| item.index), Some(Index::new(3)));
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn session_refines_like_fresh_search() {
+        fn check<T: Trie + Default>(mut tl: TriedoList<T>) {
+            for description in ["water plants", "wash car", "walk dog", "write report", "wax floor", "sweep"] {
+                tl.push(words(description), Vec::new());
+            }
+            tl.done_with_index(Index::new(4));
+            let mut session = tl.start_session();
+            assert_eq!(indices(tl.session_results(&session)), vec![0, 1, 2, 3, 5]);
+            for c in "wat".chars() {
+                let count = tl.refine(&mut session, c);
+                let fresh = search_words(&tl, session.term());
+                assert_eq!(indices(tl.session_results(&session)), fresh, "after {:?}", session.term());
+                assert_eq!(count, fresh.len());
+            }
+            assert_eq!(session.term(), "wat");
+            assert_eq!(indices(tl.session_results(&session)), vec![0]);
+        }
+        check(TriedoList::<Trie1>::new());
+        check(TriedoList::<Trie3>::new());
+        check(TriedoList::<Trie4>::new());
+        check(TriedoList::<RadixTrie>::new());
+    }
+
+    #[test]
+    fn done_items_under_each_policy() {
+        fn check<T: Trie + Default>(policy: DonePolicy) {
+            let mut tl = TriedoList::<T>::new().with_done_policy(policy);
+            for description in ["water plants", "wash car", "walk dog", "write report"] {
+                tl.push(words(description), Vec::new());
+            }
+            tl.done_with_index(Index::new(2));
+            tl.done_with_index(Index::new(0));
+            assert_eq!(tl.done_with_index(Index::new(0)), Some(Index::new(0))); //done already
+            assert!(tl.get(Index::new(2)).unwrap().done);
+            assert_eq!(indices(tl.done_items()), vec![0, 2]);
+            assert_eq!(indices(tl.recently_done(5)), vec![0, 2]);
+            assert_eq!(search_words(&tl, "w"), vec![1, 3]);
+            assert_eq!(tl.set_done_note(Index::new(2), Some(String::from("twice"))), Some(Index::new(2)));
+            assert_eq!(tl.get(Index::new(2)).unwrap().done_note.as_deref(), Some("twice"));
+            assert_eq!(crate::runner::run_line("set-done 2 false", &mut tl), Some(QueryResult::Toggled(false)));
+            assert_eq!(search_words(&tl, "w"), vec![1, 2, 3]);
+            assert_eq!(indices(tl.done_items()), vec![0]);
+            assert_eq!(tl.archive().len(), if policy == DonePolicy::MoveToArchive { 1 } else { 0 });
+            assert_eq!(crate::runner::run_line("set-done 0 true", &mut tl), Some(QueryResult::Toggled(true)));
+            assert!(tl.get(Index::new(0)).unwrap().done);
+        }
+        for policy in [DonePolicy::KeepInItems, DonePolicy::RemoveFromTrie, DonePolicy::MoveToArchive] {
+            check::<Trie4>(policy);
+            check::<RadixTrie>(policy);
+        }
+    }
+
+    #[test]
+    fn reindex_makes_indices_contiguous() {
+        fn check<T: TodoLister>(mut tl: T) {
+            for description in ["water plants", "wash car", "walk dog", "write report", "wax floor"] {
+                tl.push(words(description), Vec::new());
+            }
+            tl.retain(|item| item.index.value() % 2 == 0); //0, 2 and 4 are left
+            tl.done_with_index(Index::new(4));
+            let mapping = tl.reindex();
+            let expected: FxHashMap<Index, Index> = [(0, 0), (2, 1), (4, 2)].iter().map(|&(a, b)| (Index::new(a), Index::new(b))).collect();
+            assert_eq!(mapping, expected);
+            assert_eq!(tl.get(Index::new(1)).unwrap().description, words("walk dog"));
+            assert!(tl.get(Index::new(2)).unwrap().done);
+            assert_eq!(search_words(&tl, "w"), vec![0, 1]); //the tries follow the new indices
+            assert_eq!(tl.next_index(), Index::new(3));
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn items_without_a_tag() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash car"), Tag::from_strings(["hme"])); //a subsequence of home, but not the tag
+            tl.push(words("walk dog"), Vec::new());
+            tl.push(words("file taxes"), Tag::from_strings(["admin"]));
+            tl.push(words("mop floor"), Tag::from_strings(["home"]));
+            tl.done_with_index(Index::new(3));
+            assert_eq!(indices(tl.items_without_tag(&Tag::new("home"))), vec![1, 2]);
+            assert_eq!(indices(tl.items_without_tag(&Tag::new("hme"))), vec![0, 2, 4]);
+            assert_eq!(indices(tl.items_without_tag(&Tag::new("admin"))), vec![0, 1, 2, 4]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn tags_match_ignoring_case() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("file taxes"), Tag::from_strings(["Urgent"]));
+            tl.push(words("water plants"), Tag::from_strings(["urgent", "Home"]));
+            assert_eq!(search_tag(&tl, "urgent"), vec![0, 1]);
+            assert_eq!(tl.get(Index::new(0)).unwrap().to_string(), "0 \"file taxes\" #Urgent");
+            assert_eq!(tl.all_tags(), Tag::from_strings(["Home", "Urgent"]));
+            //retag finds the old tag by key, and merges rather than leaving two tags with the same key
+            assert_eq!(tl.retag_with_index(Index::new(1), Tag::new("HOME"), Tag::new("URGENT")), Some(Index::new(1)));
+            assert_eq!(tl.get(Index::new(1)).unwrap().tags, Tag::from_strings(["urgent"]));
+            assert_eq!(tl.retag_with_index(Index::new(0), Tag::new("urgent"), Tag::new("URGENT")), Some(Index::new(0)));
+            assert_eq!(tl.get(Index::new(0)).unwrap().tags, Tag::from_strings(["URGENT"]));
+            assert_eq!(search_tag(&tl, "urgent"), vec![0, 1]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn search_within_earlier_results() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            tl.push(words("wash car"), Vec::new());
+            tl.push(words("water lawn"), Tag::from_strings(["home"]));
+            tl.push(words("walk dog"), Tag::from_strings(["home"]));
+            tl.push(words("water the garden"), Vec::new());
+            let wa: Vec<Index> = tl.search(SearchParams::new(vec![SearchWordOrTag::RawWord("wa".to_string())]))
+                .into_iter().filter(|item| item.index.value() != 3).map(|item| item.index).collect();
+            let home = || SearchParams::new(vec![SearchWordOrTag::RawTag("home".to_string())]);
+            assert_eq!(indices(tl.search_within(home(), &wa)), vec![0, 2]); //3 has the tag, but isn't within
+            assert_eq!(indices(tl.search_within(home().with_offset(1), &wa)), vec![2]);
+            assert_eq!(indices(tl.search_within(home(), &[Index::new(4), Index::new(2), Index::new(9)])), vec![2]);
+            assert_eq!(indices(tl.search_within(home(), &[])), Vec::<u64>::new());
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn swap_two_items() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            tl.push(words("wash car"), Vec::new());
+            tl.push(words("walk dog"), Tag::from_strings(["pets"]));
+            assert!(tl.swap(Index::new(0), Index::new(2)));
+            assert_eq!(tl.get(Index::new(0)).unwrap().description, words("walk dog"));
+            assert_eq!(tl.get(Index::new(2)).unwrap().description, words("water plants"));
+            assert_eq!(search_words(&tl, "water"), vec![2]);
+            assert_eq!(search_words(&tl, "dog"), vec![0]);
+            assert_eq!(search_tag(&tl, "home"), vec![2]);
+            assert_eq!(search_tag(&tl, "pets"), vec![0]);
+            assert_eq!(indices(tl.search(SearchParams::new(Vec::new()))), vec![0, 1, 2]);
+            assert!(!tl.swap(Index::new(1), Index::new(7)));
+            assert_eq!(tl.get(Index::new(1)).unwrap().description, words("wash car"));
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn duplicate_items() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash car"), Vec::new());
+            tl.push(words("water plants"), Tag::from_strings(["Weekly", "home"])); //tag order and case don't matter
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            assert_eq!(tl.find_duplicates(), vec![vec![Index::new(0), Index::new(2)]]);
+            tl.push(words("wash car"), Vec::new());
+            tl.push(words("wash car"), Vec::new());
+            tl.done_with_index(Index::new(5)); //done items aren't counted
+            assert_eq!(tl.find_duplicates(), vec![vec![Index::new(0), Index::new(2)], vec![Index::new(1), Index::new(4)]]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn memory_usage_follows_items() {
+        fn check<T: TodoLister>(mut tl: T) {
+            let empty = tl.memory_usage();
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            let one = tl.memory_usage();
+            assert!(one > empty);
+            tl.push(words("wash the car before the weekend"), Tag::from_strings(["car", "weekly"]));
+            tl.push(words("walk dog"), Vec::new());
+            let three = tl.memory_usage();
+            assert!(three > one);
+            tl.done_with_index(Index::new(1));
+            tl.done_with_index(Index::new(2));
+            tl.retain(|item| !item.done);
+            assert!(tl.memory_usage() < three);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn most_used_tags() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash car"), Tag::from_strings(["Car", "home"]));
+            tl.push(words("fix car"), Tag::from_strings(["car", "home"]));
+            tl.push(words("mow lawn"), Tag::from_strings(["weekly", "garden"]));
+            tl.push(words("file taxes"), Tag::from_strings(["admin", "home", "car"]));
+            tl.done_with_index(Index::new(4));
+            assert_eq!(tl.top_tags(3), vec![(Tag::new("home"), 3), (Tag::new("Car"), 2), (Tag::new("weekly"), 2)]);
+            assert_eq!(tl.top_tags(10).len(), 4);
+            assert_eq!(tl.top_tags(10)[3], (Tag::new("garden"), 1));
+            assert_eq!(tl.top_tags(0), Vec::new());
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn clear_an_items_tags() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash car"), Tag::from_strings(["home"]));
+            assert_eq!(tl.clear_tags(Index::new(0)), Some(2));
+            assert_eq!(search_tag(&tl, "home"), vec![1]);
+            assert_eq!(search_tag(&tl, "weekly"), Vec::<u64>::new());
+            assert_eq!(search_words(&tl, "water"), vec![0]);
+            assert_eq!(tl.get(Index::new(0)).unwrap().description, words("water plants"));
+            assert_eq!(tl.clear_tags(Index::new(0)), Some(0));
+            assert_eq!(tl.clear_tags(Index::new(5)), None);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn word_warning_past_threshold() {
+        thread_local! {
+            static WARNED: std::cell::RefCell<Vec<(String, usize)>> = std::cell::RefCell::new(Vec::new());
+        }
+        fn on_large(word: &str, n: usize) {
+            WARNED.with(|warned| warned.borrow_mut().push((word.to_string(), n)));
+        }
+        fn check<T: Trie + Default + PartialEq + fmt::Debug>() {
+            WARNED.with(|warned| warned.borrow_mut().clear());
+            let mut tl = TriedoList::<T>::new().with_word_warning(2, on_large);
+            let mut plain = TriedoList::<T>::new();
+            for description in ["buy the milk", "walk the dog", "the the end", "wash the car", "buy bread"] {
+                tl.push(words(description), Vec::new());
+                plain.push(words(description), Vec::new());
+            }
+            tl.edit(Index::new(4), words("buy the bread"));
+            WARNED.with(|warned| assert_eq!(*warned.borrow(), vec![(String::from("the"), 3)])); //an item with the word twice counts once
+            assert_eq!(tl.len(), plain.len());
+            plain.edit(Index::new(4), words("buy the bread"));
+            assert_eq!(tl, plain); //the warning isn't compared
+        }
+        check::<Trie1>();
+        check::<Trie4>();
+        check::<CharTrie>();
+        check::<RadixTrie>();
+    }
+
+    #[test]
+    fn rebuild_corrupted_tries() {
+        fn check<T: Trie + Default + PartialEq + fmt::Debug + Clone>() {
+            let mut tl = TriedoList::<T>::new();
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            tl.push(words("wash car"), Tag::from_strings(["car"]));
+            tl.push(words("walk dog"), Vec::new());
+            tl.done_with_index(Index::new(2));
+            let mut expected = tl.clone();
+            expected.rebuild_indices(); //without the nodes left behind by done, to compare with
+            tl.words.delete(0);
+            tl.lower_words.delete(0);
+            tl.words.add(2, vec!["zebra"]);
+            tl.tags.delete(0);
+            assert_eq!(search_words(&tl, "water"), Vec::<u64>::new());
+            assert_eq!(search_tag(&tl, "home"), Vec::<u64>::new());
+            tl.rebuild_indices();
+            assert_eq!(search_words(&tl, "water"), vec![0]);
+            assert_eq!(search_words(&tl, "zebra"), Vec::<u64>::new());
+            assert_eq!(search_words(&tl, "wa"), vec![0, 1]);
+            assert_eq!(search_tag(&tl, "home"), vec![0]);
+            assert_eq!(tl, expected);
+        }
+        check::<Trie1>();
+        check::<Trie2>();
+        check::<Trie3>();
+        check::<Trie4>();
+        check::<CharTrie>();
+        check::<RadixTrie>();
+    }
+
+    #[test]
+    fn recently_done_by_completion() {
+        fn check<T: TodoLister>(mut tl: T) {
+            for description in ["water plants", "wash car", "walk dog", "file taxes"] {
+                tl.push(words(description), Vec::new());
+            }
+            for i in [3, 0, 2] {
+                tl.done_with_index(Index::new(i));
+            }
+            assert_eq!(indices(tl.recently_done(10)), vec![2, 0, 3]);
+            assert_eq!(indices(tl.recently_done(2)), vec![2, 0]);
+            tl.toggle_with_index(Index::new(0)); //reopened, then done again later
+            assert_eq!(indices(tl.recently_done(10)), vec![2, 3]);
+            tl.done_with_index(Index::new(0));
+            assert_eq!(indices(tl.recently_done(10)), vec![0, 2, 3]);
+            assert_eq!(indices(tl.recently_done(0)), Vec::<u64>::new());
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn partition_on_done() {
+        fn check<T: TodoLister + Clone>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home"]));
+            tl.push(words("wash car"), Tag::from_strings(["home"]));
+            tl.push(words("walk dog"), Vec::new());
+            tl.done_with_index(Index::new(1));
+            let (active, mut done) = tl.partition(|item| !item.done);
+            assert_eq!(active.len(), 2);
+            assert_eq!(done.len(), 1);
+            assert_eq!(search_words(&active, "wa"), vec![0, 2]);
+            assert_eq!(search_tag(&active, "home"), vec![0]);
+            assert_eq!(indices(done.done_items()), vec![1]);
+            assert_eq!(search_words(&done, "wa"), Vec::<u64>::new());
+            done.toggle_with_index(Index::new(1));
+            assert_eq!(search_tag(&done, "home"), vec![1]); //reopened, its tries have it
+            assert_eq!(done.push(words("mop floor"), Vec::new()).index, Index::new(3)); //both keep the next index
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn any_words_all_tags() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash car"), Tag::from_strings(["home"]));
+            tl.push(words("walk dog"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("file taxes"), Tag::from_strings(["home", "weekly"]));
+            tl.push(words("wash dishes"), Tag::from_strings(["weekly", "home", "kitchen"]));
+            tl.push(words("mop floor"), Tag::from_strings(["weekly"]));
+            let terms = vec![
+                SearchWordOrTag::RawWord("water".to_string()),
+                SearchWordOrTag::RawWord("dishes".to_string()),
+                SearchWordOrTag::RawWord("car".to_string()),
+                SearchWordOrTag::RawTag("home".to_string()),
+                SearchWordOrTag::RawTag("weekly".to_string()),
+            ];
+            let search = |tl: &T, combine, tag_combine| indices(tl.search(SearchParams::new(terms.clone()).with_combine(combine).with_tag_combine(tag_combine)));
+            //any of the words, and both tags: not 1 (only #home), 2 or 3 (none of the words), or 5 (neither)
+            assert_eq!(search(&tl, CombineMode::Any, CombineMode::All), vec![0, 4]);
+            assert_eq!(search(&tl, CombineMode::Any, CombineMode::Any), vec![0, 1, 4]);
+            assert_eq!(search(&tl, CombineMode::All, CombineMode::All), Vec::<u64>::new());
+            tl.done_with_index(Index::new(4));
+            assert_eq!(search(&tl, CombineMode::Any, CombineMode::All), vec![0]);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn truncated_display() {
+        let long = TodoItem::new(Index::new(3), words("walk the dog around the park"), Tag::from_strings(["pets"]), false);
+        let short = TodoItem::new(Index::new(4), words("wash car"), Vec::new(), false);
+        assert_eq!(long.display_truncated(3).to_string(), "3 \"walk the dog...\" #pets");
+        assert_eq!(long.display_truncated(6).to_string(), long.to_string());
+        assert_eq!(short.display_truncated(3).to_string(), "4 \"wash car\" ");
+        assert_eq!(short.display_truncated(2).to_string(), short.to_string());
+        assert_eq!(long.display_truncated(0).to_string(), "3 \"walk...\" #pets"); //at least one word
+        assert_eq!(long.to_string(), "3 \"walk the dog around the park\" #pets"); //the item itself is unchanged
+    }
+
+    #[test]
+    fn pages_of_live_items() {
+        fn check<T: TodoLister>(mut tl: T) {
+            for n in 0..7 {
+                tl.push(words(&format!("task {}", "x".repeat(n + 1))), Vec::new());
+            }
+            tl.done_with_index(Index::new(2));
+            tl.done_with_index(Index::new(5));
+            let page = |offset, limit| { let (items, total) = tl.page(offset, limit); (indices(items), total) };
+            assert_eq!(page(0, 2), (vec![0, 1], 5));
+            assert_eq!(page(2, 2), (vec![3, 4], 5));
+            assert_eq!(page(4, 2), (vec![6], 5));
+            assert_eq!(page(6, 2), (Vec::new(), 5));
+            assert_eq!(page(0, usize::MAX), (vec![0, 1, 3, 4, 6], 5));
+            assert_eq!(page(1, 0), (Vec::new(), 5));
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn hashed_keys_dedup() {
+        use std::collections::HashSet;
+        let indices: HashSet<Index> = vec![Index::new(3), Index::new(1), Index::new(3)].into_iter().collect();
+        assert_eq!(indices.len(), 2);
+        assert!(indices.contains(&Index::new(1)));
+        let words: HashSet<Word> = words("water the plants the water").into_iter().collect();
+        assert_eq!(words.len(), 3);
+        assert!(words.contains(&Word::new("the").unwrap()));
+        let tags: HashSet<Tag> = Tag::from_strings(["home", "work", "home"]).into_iter().collect();
+        assert_eq!(tags.len(), 2);
+        assert!(!tags.contains(&Tag::new("Home"))); //hashed as written, like Eq, rather than by key
+    }
+
+    #[test]
+    fn done_by_raw_or_index() {
+        fn check<T: TodoLister + Clone>(mut tl: T) {
+            tl.push(words("water plants"), Vec::new());
+            tl.push(words("wash car"), Vec::new());
+            let mut typed = tl.clone();
+            assert_eq!(tl.done_by_raw(1), Some(Index::new(1)));
+            assert_eq!(typed.done_with_index(Index::new(1)), Some(Index::new(1)));
+            assert_eq!(indices(tl.done_items()), indices(typed.done_items()));
+            assert_eq!(search_words(&tl, "wa"), vec![0]);
+            assert_eq!(tl.done_by_raw(7), None);
+            assert_eq!(typed.done_with_index(Index::new(7)), None);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn bump_priority_bounds() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("water plants"), Vec::new());
+            assert_eq!(tl.bump_priority(Index::new(0), 3), Some(3));
+            assert_eq!(tl.bump_priority(Index::new(0), -4), Some(0));
+            assert_eq!(tl.bump_priority(Index::new(0), 300), Some(255));
+            assert_eq!(tl.bump_priority(Index::new(0), i32::MAX), Some(255));
+            assert_eq!(tl.bump_priority(Index::new(0), i32::MIN), Some(0));
+            assert_eq!(tl.get(Index::new(0)).unwrap().priority, 0);
+            assert_eq!(tl.bump_priority(Index::new(1), 1), None);
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn successive_pages_cover_results() {
+        fn check<T: TodoLister>(mut tl: T) {
+            for n in 0..11 {
+                tl.push(words(&format!("water {}", "x".repeat(n + 1))), Vec::new());
+                tl.push(words("walk dog"), Vec::new());
+            }
+            tl.done_with_index(Index::new(4));
+            let sp = || SearchParams::new(vec![SearchWordOrTag::RawWord("water".to_string())]);
+            let full = indices(tl.search(sp()));
+            assert_eq!(full.len(), 10);
+            let mut paged = Vec::new();
+            for page in 0..4 {
+                let results = indices(tl.search(sp().with_offset(page * 3).with_limit(3)));
+                assert!(results.len() <= 3);
+                paged.extend(results);
+            }
+            assert_eq!(paged, full); //no overlaps or gaps between the pages
+            assert_eq!(indices(tl.search(sp().with_offset(12).with_limit(3))), Vec::<u64>::new());
+        }
+        for_each_list!(check);
+    }
+
+    #[test]
+    fn leap_days() {
+        assert!(Date::new(2024, 2, 29).is_some());
+        assert!(Date::new(2023, 2, 29).is_none());
+        assert!(Date::new(1900, 2, 29).is_none());
+        assert!(Date::new(2000, 2, 29).is_some());
+        assert!(Date::new(2023, 4, 31).is_none());
+        assert!(Date::new(2023, 13, 1).is_none());
+    }
+
+    #[test]
+    fn tag_terms_are_folded_like_tags() {
+        fn check<T: TodoLister>(mut tl: T) {
+            tl.push(words("fix login"), Tag::from_strings(["urgent"]));
+            tl.push(words("file taxes"), Tag::from_strings(["Urgent", "home"]));
+            tl.push(words("urgent call"), Vec::new());
+            let search = |term: SearchWordOrTag| indices(tl.search(SearchParams::new(vec![term]))); //tags_ignore_case is off
+            assert_eq!(search(SearchWordOrTag::RawTag("Urgent".to_string())), vec![0, 1]);
+            assert_eq!(search(SearchWordOrTag::WithMode(MatchMode::Exact, Box::new(SearchWordOrTag::RawTag("URGENT".to_string())))), vec![0, 1]);
+            assert_eq!(search(SearchWordOrTag::RawAny("Urgent".to_string())), vec![0, 1]); //words keep their case
+            assert_eq!(search(SearchWordOrTag::RawAny("urgent".to_string())), vec![0, 1, 2]);
+        }
+        for_each_list!(check);
+    }
+}
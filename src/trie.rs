@@ -1,13 +1,297 @@
 //Various experiments building an efficient Trie to replace Naive implementation
 
+use std::mem;
+
 use rustc_hash::{FxHashSet, FxHashMap};
 
-const CHARS: [char; 27] = ['a','b','c','d','e','f','g','h','i','j','k','l','m','n','o','p','q','r','s','t','u','v','w','x','y','z','-'];
+const CHARS: [char; 40] = ['a','b','c','d','e','f','g','h','i','j','k','l','m','n','o','p','q','r','s','t','u','v','w','x','y','z','-',' ','/','#','0','1','2','3','4','5','6','7','8','9']; //space for quoted tags, / for tag hierarchies, # and digits for escaped words like \#1
 
 pub trait Trie { 
     fn add(&mut self, id: u64, inserts: Vec<&str>);
-    fn search(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64>;
+    //adds the same word for every id, descending the trie only once
+    fn add_many(&mut self, ids: &[u64], insert: &str);
+    //ids marked done aren't found, see search_with_done
+    fn search(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64> {
+        self.search_with_done(searches, filter, false)
+    }
+    //like search, also finding the ids marked done when include_done is set
+    fn search_with_done(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64>;
+    //marks an id done (or not done again), each node keeps the ids below it that are done apart from the rest,
+    //so searches can skip them as they go, ids are added not done and should be marked after all their words are added
+    fn set_done(&mut self, id: u64, done: bool);
     fn delete(&mut self, id: u64);
+    //direct children of this node, ordered by their char
+    fn children(&self) -> Vec<(char, &Self)>;
+    //ids which have a word ending at this node, with the number of their words which end here
+    fn ends(&self) -> &FxHashMap<u64, usize>;
+    //removes every word, keeping this node's allocated capacity (the child nodes themselves are freed)
+    fn clear(&mut self);
+    //number of nodes in the trie, including this one
+    fn node_count(&self) -> usize where Self: Sized {
+        let mut count = 0;
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            count += 1;
+            tries_to_visit.extend(trie.children().into_iter().map(|(_, child)| child));
+        }
+        count
+    }
+    //number of distinct words stored, a word whose ids have all been deleted isn't counted
+    fn word_count(&self) -> usize where Self: Sized {
+        let mut count = 0;
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            if !trie.ends().is_empty() {
+                count += 1;
+            }
+            tries_to_visit.extend(trie.children().into_iter().map(|(_, child)| child));
+        }
+        count
+    }
+    //checks the structure is consistent, for debugging: children in strictly ascending order of their char,
+    //a positive count for every id ending at a node, and every stored word found by searching for it (including done ids)
+    //empty nodes aren't checked for: deleting leaves them behind until the trie is rebuilt, so they're allowed
+    //and index sets aren't checked for order, the ids ending at a node are a hash map and search_sorted sorts its own result
+    #[cfg(debug_assertions)]
+    fn check_invariants(&self) -> Result<(), String> where Self: Sized {
+        let mut tries_to_visit = vec![(self, String::new())];
+        while let Some((trie, path)) = tries_to_visit.pop() {
+            let children = trie.children();
+            if let Some(pair) = children.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+                return Err(format!("children of \"{}\" are out of order at '{}' and '{}'", path, pair[0].0, pair[1].0))
+            }
+            if let Some((id, _)) = trie.ends().iter().find(|(_, count)| **count == 0) {
+                return Err(format!("id {} ends at \"{}\" zero times", id, path))
+            }
+            if !trie.ends().is_empty() {
+                let found_done = self.search_with_done(vec![&path], None, true);
+                if let Some(id) = trie.ends().keys().find(|id| !found_done.contains(id)) {
+                    return Err(format!("id {} ends at \"{}\" but isn't found by searching for it", id, path))
+                }
+            }
+            for (c, child) in children {
+                let mut child_path = path.clone();
+                child_path.push_str(&child.edge_text(c));
+                tries_to_visit.push((child, child_path));
+            }
+        }
+        Ok(())
+    }
+    //the text on the edge into this node from its parent, which reaches it with c (longer for path compressed tries)
+    fn edge_text(&self, c: char) -> String where Self: Sized {
+        c.to_string()
+    }
+    //a Graphviz digraph of the trie for debugging, nodes are labeled by their edge text and numbered as they are reached
+    //nodes where words end are drawn with a double outline, with the number of ids ending there in brackets
+    fn to_dot(&self) -> String where Self: Sized {
+        let mut lines = vec![String::from("digraph trie {"), String::from("    n0 [label=\"\"];")];
+        let mut next_id = 1;
+        let mut tries_to_visit: Vec<(usize, &Self)> = vec![(0, self)];
+        while let Some((id, trie)) = tries_to_visit.pop() {
+            let mut children = Vec::new();
+            for (c, child) in trie.children() {
+                let label = child.edge_text(c).replace('\\', "\\\\").replace('"', "\\\"");
+                if child.ends().is_empty() {
+                    lines.push(format!("    n{} [label=\"{}\"];", next_id, label));
+                }
+                else {
+                    lines.push(format!("    n{} [label=\"{} ({})\", peripheries=2];", next_id, label, child.ends().len()));
+                }
+                lines.push(format!("    n{} -> n{};", id, next_id));
+                children.push((next_id, child));
+                next_id += 1;
+            }
+            tries_to_visit.extend(children.into_iter().rev()); //so the first child is visited next
+        }
+        lines.push(String::from("}"));
+        lines.join("\n")
+    }
+    //ids matching a single search, in ascending order
+    //ids are distinct (search returns a set), even if an item has several words matching the search
+    fn search_sorted(&self, search: &str) -> Vec<u64> {
+        let mut results: Vec<u64> = self.search(vec![search], None).into_iter().collect();
+        results.sort_unstable();
+        results
+    }
+    //every stored word in alphabetical order, with the ids it's stored for (and how many times each), words whose ids have all been deleted are skipped
+    fn iter_terminals(&self) -> impl Iterator<Item = (String, &FxHashMap<u64, usize>)> where Self: Sized {
+        let mut tries_to_visit = vec![(self, String::new())];
+        std::iter::from_fn(move || {
+            while let Some((trie, path)) = tries_to_visit.pop() {
+                for (c, child) in trie.children().into_iter().rev() { //so the first child is visited next
+                    let mut child_path = path.clone();
+                    child_path.push_str(&child.edge_text(c));
+                    tries_to_visit.push((child, child_path));
+                }
+                if !trie.ends().is_empty() {
+                    return Some((path, trie.ends()))
+                }
+            }
+            None
+        })
+    }
+    //like search for a single key, but with how many of each id's words the key matched (a repeated word counts each time)
+    //the key is matched along each path like search does, and once it's used up every word ending below there matches
+    fn search_with_counts(&self, key: &str) -> FxHashMap<u64, usize> where Self: Sized {
+        let mut counts = FxHashMap::default();
+        let mut tries_to_visit = vec![(self, key)];
+        while let Some((trie, rest)) = tries_to_visit.pop() {
+            if rest.is_empty() {
+                let mut matched = vec![trie];
+                while let Some(trie) = matched.pop() {
+                    for (id, count) in trie.ends() {
+                        *counts.entry(*id).or_insert(0) += count;
+                    }
+                    matched.extend(trie.children().into_iter().map(|(_, child)| child));
+                }
+                continue
+            }
+            for (c, child) in trie.children() {
+                let mut child_rest = rest;
+                for edge_c in child.edge_text(c).chars() { //taking each char as soon as it matches is never worse for a subsequence
+                    child_rest = child_rest.strip_prefix(edge_c).unwrap_or(child_rest);
+                }
+                tries_to_visit.push((child, child_rest));
+            }
+        }
+        counts
+    }
+    //the ids a word is stored for (as a whole word, not a prefix or subsequence of a longer one), with how many times each
+    fn ends_at(&self, word: &str) -> Option<&FxHashMap<u64, usize>> where Self: Sized {
+        let mut trie = self;
+        let mut rest = word;
+        while let Some(c) = rest.chars().next() {
+            let (_, child) = trie.children().into_iter().find(|(child_c, _)| *child_c == c)?;
+            rest = rest.strip_prefix(&child.edge_text(c)[..])?; //a compressed edge must be matched whole
+            trie = child;
+        }
+        Some(trie.ends()).filter(|ends| !ends.is_empty())
+    }
+    //like add, also calling on_large with each word whose number of ids grows past the threshold with this add (and that number)
+    //very common words (stop-words) have huge sets of ids which make intersecting them slow, this is for spotting them
+    fn add_checked(&mut self, id: u64, inserts: Vec<&str>, threshold: usize, on_large: &mut dyn FnMut(&str, usize)) where Self: Sized {
+        let before: Vec<usize> = inserts.iter().map(|word| self.ends_at(word).map_or(0, FxHashMap::len)).collect();
+        self.add(id, inserts.clone());
+        for (n, (word, before)) in inserts.iter().zip(before).enumerate() {
+            if inserts[..n].contains(word) { //a word given twice is only reported once
+                continue
+            }
+            let after = self.ends_at(word).map_or(0, FxHashMap::len);
+            if before <= threshold && after > threshold {
+                on_large(word, after);
+            }
+        }
+    }
+    //the longest stored word which is a prefix of the key (or the key itself), with the ids it's stored for in ascending order
+    //unlike search only whole stored words count, an empty string and no ids if there isn't one
+    fn longest_prefix(&self, key: &str) -> (String, Vec<u64>) where Self: Sized {
+        let (mut path, mut longest) = (String::new(), (String::new(), Vec::new()));
+        let mut trie = self;
+        loop {
+            if !trie.ends().is_empty() {
+                let mut ids: Vec<u64> = trie.ends().keys().copied().collect();
+                ids.sort_unstable();
+                longest = (path.clone(), ids);
+            }
+            let rest = &key[path.len()..];
+            let Some(c) = rest.chars().next() else {
+                break
+            };
+            match trie.children().into_iter().find(|(child_c, _)| *child_c == c) {
+                Some((_, child)) if rest.starts_with(&child.edge_text(c)) => { //a compressed edge must be matched whole
+                    path.push_str(&child.edge_text(c));
+                    trie = child;
+                },
+                _ => break,
+            }
+        }
+        longest
+    }
+    //stored words for which some prefix is within max_dist edits (insertions, deletions or substitutions) of the given prefix
+    //results are ordered by fewest edits and then alphabetically, at most limit of them are returned
+    fn complete_word_fuzzy(&self, prefix: &str, max_dist: usize, limit: usize) -> Vec<String> where Self: Sized {
+        let prefix: Vec<char> = prefix.chars().collect();
+        let mut completions = Vec::new();
+        //each trie to visit has the edit distance between the prefix and its path (as a row of the usual dp table),
+        //and the smallest distance between the whole prefix and any part of the path (meaning everything below is a completion)
+        let mut tries_to_visit = vec![(self, String::new(), (0..=prefix.len()).collect::<Vec<usize>>(), None)];
+        while let Some((trie, path, row, matched)) = tries_to_visit.pop() {
+            let matched = match (matched, row[prefix.len()]) {
+                (Some(best), dist) => Some(dist.min(best)),
+                (None, dist) if dist <= max_dist => Some(dist),
+                (None, _) => None,
+            };
+            if let Some(dist) = matched {
+                if !trie.ends().is_empty() {
+                    completions.push((dist, path.clone()));
+                }
+            }
+            else if row.iter().min().unwrap() > &max_dist { //no extension of this path can get close enough to the prefix
+                continue
+            }
+            for (c, child) in trie.children() {
+                let mut next_path = path.clone();
+                next_path.push(c);
+                tries_to_visit.push((child, next_path, next_edit_row(&prefix, &row, c), matched));
+            }
+        }
+        completions.sort();
+        completions.into_iter().take(limit).map(|(_, word)| word).collect()
+    }
+}
+
+//the next row of the edit distance table between the prefix and a path, after extending the path with c
+fn next_edit_row(prefix: &[char], row: &[usize], c: char) -> Vec<usize> {
+    let mut next_row = vec![row[0] + 1];
+    for (j, p) in prefix.iter().enumerate() {
+        let substitution = row[j] + if *p == c { 0 } else { 1 };
+        next_row.push(substitution.min(row[j + 1] + 1).min(next_row[j] + 1));
+    }
+    next_row
+}
+
+//merges two ascending lists of ids, keeping only those present in both (result is also ascending)
+pub fn intersect_sorted(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut results = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            i += 1;
+        }
+        else if a[i] > b[j] {
+            j += 1;
+        }
+        else {
+            results.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    results
+}
+
+//merges two ascending lists of ids, keeping those present in either (result is also ascending and deduped)
+pub fn union_sorted(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let mut results = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            results.push(a[i]);
+            i += 1;
+        }
+        else if a[i] > b[j] {
+            results.push(b[j]);
+            j += 1;
+        }
+        else {
+            results.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    results.extend_from_slice(&a[i..]);
+    results.extend_from_slice(&b[j..]);
+    results
 }
 
 //non-recursive, search-match pruning and depth pruning
@@ -15,53 +299,69 @@ pub trait Trie {
 pub struct Trie4 {
     children: FxHashMap<char, Trie4>,
     id_to_depth: FxHashMap<u64, usize>,
+    done_to_depth: FxHashMap<u64, usize>, //like id_to_depth, for the ids marked done
+    ends: FxHashMap<u64, usize>,
 }
 impl Trie4 {
     fn new() -> Self {
         Trie4{
             children: FxHashMap::default(),
             id_to_depth: FxHashMap::default(),
+            done_to_depth: FxHashMap::default(),
+            ends: FxHashMap::default(),
         }
     }
-    fn add_single(&mut self, id: u64, insert: &str) {
+    //the depths of the ids a search can find
+    fn depths(&self, include_done: bool) -> impl Iterator<Item = (&u64, &usize)> {
+        let done = if include_done { Some(self.done_to_depth.iter()) } else { None };
+        self.id_to_depth.iter().chain(done.into_iter().flatten())
+    }
+    fn add_single(&mut self, ids: &[u64], insert: &str) {
         let mut trie = self;
         let mut new_depth = insert.len();
         
-        trie.id_to_depth.entry(id)
-            .and_modify(|current_depth| {
-                if new_depth > *current_depth {
-                    *current_depth = new_depth;
-                }
-            })
-            .or_insert(new_depth);
+        for id in ids {
+            trie.id_to_depth.entry(*id)
+                .and_modify(|current_depth| {
+                    if new_depth > *current_depth {
+                        *current_depth = new_depth;
+                    }
+                })
+                .or_insert(new_depth);
+        }
 
         for c in insert.chars() {
             trie = trie.children.entry(c).or_insert(Trie4::new());
             new_depth -= 1;
 
-            trie.id_to_depth.entry(id)
-            .and_modify(|current_depth| {
-                if new_depth > *current_depth {
-                    *current_depth = new_depth;
-                }
-            })
-            .or_insert(new_depth);
+            for id in ids {
+                trie.id_to_depth.entry(*id)
+                .and_modify(|current_depth| {
+                    if new_depth > *current_depth {
+                        *current_depth = new_depth;
+                    }
+                })
+                .or_insert(new_depth);
+            }
+        }
+        for id in ids {
+            *trie.ends.entry(*id).or_insert(0) += 1;
         }
     }
-    fn search_single(&self, search: &str, filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64> {
+    fn search_single(&self, search: &str, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
         let mut results = FxHashSet::default();
         let mut tries_to_visit = vec![(self, search)];
         'trie: while let Some((trie, search)) = tries_to_visit.pop() {
 
             let max_depth;
             if let Some(filter) = filter {
-                max_depth = trie.id_to_depth.keys()
-                                            .filter(|key| filter.contains(key))
-                                            .map(|key| trie.id_to_depth.get(key).unwrap())
-                                            .max();
+                max_depth = trie.depths(include_done)
+                                .filter(|(key, _)| filter.contains(key))
+                                .map(|(_, depth)| depth)
+                                .max();
             }
             else {
-                max_depth = trie.id_to_depth.values().max();
+                max_depth = trie.depths(include_done).map(|(_, depth)| depth).max();
             }
             if let Some(max_depth) = max_depth {
                 if search.len() > *max_depth { //match is impossible because trie is not deep enough, so skip
@@ -81,8 +381,7 @@ impl Trie4 {
                 }
             }
             else {
-                let ids = &trie.id_to_depth.keys().cloned().collect();
-                results = results.union(ids).cloned().collect();
+                results.extend(trie.depths(include_done).map(|(id, _)| *id));
             }
 
         }
@@ -92,15 +391,18 @@ impl Trie4 {
 impl Trie for Trie4 {
     fn add(&mut self, id: u64, inserts: Vec<&str>) {
         for insert in inserts {
-            Self::add_single(self, id, insert)
+            Self::add_single(self, &[id], insert)
         }
     }
-    fn search(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64> {
+    fn add_many(&mut self, ids: &[u64], insert: &str) {
+        Self::add_single(self, ids, insert)
+    }
+    fn search_with_done(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
         let mut searches = searches.iter();
         if let Some(first_search) = searches.next() {
-            let mut result = Self::search_single(self, first_search, filter);
+            let mut result = Self::search_single(self, first_search, filter, include_done);
             for search in searches { //use results of previous searches to filter ids in subsequent searches
-                result = result.intersection(&Self::search_single(self, search, Some(&result))).cloned().collect();
+                result = result.intersection(&Self::search_single(self, search, Some(&result), include_done)).cloned().collect();
             }
             result
         }
@@ -108,16 +410,43 @@ impl Trie for Trie4 {
             FxHashSet::default()
         }
     }
+    fn set_done(&mut self, id: u64, done: bool) {
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            let (from, to) = if done { (&mut trie.id_to_depth, &mut trie.done_to_depth) } else { (&mut trie.done_to_depth, &mut trie.id_to_depth) };
+            if let Some(depth) = from.remove(&id) {
+                to.insert(id, depth);
+                for new_trie in trie.children.values_mut() {
+                    tries_to_visit.push(new_trie)
+                }
+            }
+        }
+    }
     fn delete(&mut self, id: u64) {
         let mut tries_to_visit = vec![self];
         while let Some(trie) = tries_to_visit.pop() {
-            if let Some(_) = trie.id_to_depth.remove(&id) {
+            if trie.id_to_depth.remove(&id).is_some() | trie.done_to_depth.remove(&id).is_some() {
+                trie.ends.remove(&id);
                 for new_trie in trie.children.values_mut() {
                     tries_to_visit.push(new_trie)
                 }
             }
         }
     }
+    fn children(&self) -> Vec<(char, &Self)> {
+        let mut children: Vec<(char, &Self)> = self.children.iter().map(|(c, trie)| (*c, trie)).collect();
+        children.sort_by_key(|(c, _)| *c);
+        children
+    }
+    fn ends(&self) -> &FxHashMap<u64, usize> {
+        &self.ends
+    }
+    fn clear(&mut self) {
+        self.children.clear();
+        self.id_to_depth.clear();
+        self.done_to_depth.clear();
+        self.ends.clear();
+    }
 }
 impl Default for Trie4 {
     fn default() -> Self {
@@ -130,30 +459,37 @@ impl Default for Trie4 {
 pub struct Trie3 {
     children: FxHashMap<char, Trie3>,
     ids: FxHashSet<u64>,
+    done_ids: FxHashSet<u64>, //ids marked done, which aren't in ids
+    ends: FxHashMap<u64, usize>,
 }
 impl Trie3 {
     fn new() -> Self {
         Trie3{
             children: FxHashMap::default(),
             ids: FxHashSet::default(),
+            done_ids: FxHashSet::default(),
+            ends: FxHashMap::default(),
         }
     }
-    fn add_single(&mut self, id: u64, insert: &str) {
+    fn add_single(&mut self, ids: &[u64], insert: &str) {
         let mut trie = self;
-        trie.ids.insert(id);
+        trie.ids.extend(ids);
         for c in insert.chars() {
             trie = trie.children.entry(c).or_insert(Trie3::new());
-            trie.ids.insert(id);
+            trie.ids.extend(ids);
+        }
+        for id in ids {
+            *trie.ends.entry(*id).or_insert(0) += 1;
         }
     }
-    fn search_single(&self, search: &str, filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64> {
+    fn search_single(&self, search: &str, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
         let mut results = FxHashSet::default();
         let mut tries_to_visit = vec![(self, search)];
         'trie: while let Some((trie, search)) = tries_to_visit.pop() {
             if let Some(f) = filter { 
                 let mut keep_searching = false;
                 for id in f { //if this trie contains an index in the filter, keep searching, otherwise skip this branch
-                    if trie.ids.contains(id) {
+                    if trie.ids.contains(id) || (include_done && trie.done_ids.contains(id)) {
                         keep_searching = true;
                         break
                     }
@@ -172,6 +508,9 @@ impl Trie3 {
             }
             else {
                 results = results.union(&trie.ids).cloned().collect();
+                if include_done {
+                    results.extend(trie.done_ids.iter().cloned());
+                }
             }
         }
         results
@@ -180,15 +519,18 @@ impl Trie3 {
 impl Trie for Trie3 {
     fn add(&mut self, id: u64, inserts: Vec<&str>) {
         for insert in inserts {
-            Self::add_single(self, id, insert)
+            Self::add_single(self, &[id], insert)
         }
     }
-    fn search(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64> {
+    fn add_many(&mut self, ids: &[u64], insert: &str) {
+        Self::add_single(self, ids, insert)
+    }
+    fn search_with_done(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
         let mut searches = searches.iter();
         if let Some(first_search) = searches.next() {
-            let mut result = Self::search_single(self, first_search, filter);
+            let mut result = Self::search_single(self, first_search, filter, include_done);
             for search in searches { //use results of previous searches to filter ids in subsequent searches
-                result = result.intersection(&Self::search_single(self, search, Some(&result))).cloned().collect();
+                result = result.intersection(&Self::search_single(self, search, Some(&result), include_done)).cloned().collect();
             }
             result
         }
@@ -196,16 +538,43 @@ impl Trie for Trie3 {
             FxHashSet::default()
         }
     }
+    fn set_done(&mut self, id: u64, done: bool) {
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            let (from, to) = if done { (&mut trie.ids, &mut trie.done_ids) } else { (&mut trie.done_ids, &mut trie.ids) };
+            if from.remove(&id) {
+                to.insert(id);
+                for new_trie in trie.children.values_mut() {
+                    tries_to_visit.push(new_trie)
+                }
+            }
+        }
+    }
     fn delete(&mut self, id: u64) {
         let mut tries_to_visit = vec![self];
         while let Some(trie) = tries_to_visit.pop() {
-            if trie.ids.remove(&id) {
+            if trie.ids.remove(&id) | trie.done_ids.remove(&id) {
+                trie.ends.remove(&id);
                 for new_trie in trie.children.values_mut() {
                     tries_to_visit.push(new_trie)
                 }
             }
         }
     }
+    fn children(&self) -> Vec<(char, &Self)> {
+        let mut children: Vec<(char, &Self)> = self.children.iter().map(|(c, trie)| (*c, trie)).collect();
+        children.sort_by_key(|(c, _)| *c);
+        children
+    }
+    fn ends(&self) -> &FxHashMap<u64, usize> {
+        &self.ends
+    }
+    fn clear(&mut self) {
+        self.children.clear();
+        self.ids.clear();
+        self.done_ids.clear();
+        self.ends.clear();
+    }
 }
 impl Default for Trie3 {
     fn default() -> Self {
@@ -218,23 +587,30 @@ impl Default for Trie3 {
 pub struct Trie2 {
     children: FxHashMap<char, Trie2>,
     ids: FxHashSet<u64>,
+    done_ids: FxHashSet<u64>, //ids marked done, which aren't in ids
+    ends: FxHashMap<u64, usize>,
 }
 impl Trie2 {
     fn new() -> Self {
         Trie2{
             children: FxHashMap::default(),
             ids: FxHashSet::default(),
+            done_ids: FxHashSet::default(),
+            ends: FxHashMap::default(),
         }
     }
-    fn add_single(&mut self, id: u64, insert: &str) {
+    fn add_single(&mut self, ids: &[u64], insert: &str) {
         let mut trie = self;
-        trie.ids.insert(id);
+        trie.ids.extend(ids);
         for c in insert.chars() {
             trie = trie.children.entry(c).or_insert(Trie2::new());
-            trie.ids.insert(id);
+            trie.ids.extend(ids);
+        }
+        for id in ids {
+            *trie.ends.entry(*id).or_insert(0) += 1;
         }
     }
-    fn search_single(&self, search: &str) -> FxHashSet<u64> {
+    fn search_single(&self, search: &str, include_done: bool) -> FxHashSet<u64> {
         let mut results = FxHashSet::default();
         let mut tries_to_visit = vec![(self, search)];
         while let Some((trie, search)) = tries_to_visit.pop() {
@@ -248,6 +624,9 @@ impl Trie2 {
             }
             else {
                 results = results.union(&trie.ids).cloned().collect();
+                if include_done {
+                    results.extend(trie.done_ids.iter().cloned());
+                }
             }
         }
         results
@@ -256,26 +635,56 @@ impl Trie2 {
 impl Trie for Trie2 {
     fn add(&mut self, id: u64, inserts: Vec<&str>) {
         for insert in inserts {
-            Self::add_single(self, id, insert)
+            Self::add_single(self, &[id], insert)
         }
     }
-    fn search(&self, searches: Vec<&str>, _filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64> {
-        let mut matches = searches.iter().map(|search| Self::search_single(self, search));
+    fn add_many(&mut self, ids: &[u64], insert: &str) {
+        Self::add_single(self, ids, insert)
+    }
+    fn search_with_done(&self, searches: Vec<&str>, _filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
+        let mut matches = searches.iter().map(|search| Self::search_single(self, search, include_done));
         if let Some(first_match) = matches.next() {
             return matches.fold(first_match, |acc, next_match| acc.intersection(&next_match).cloned().collect())
         }
         FxHashSet::default()
     }
+    fn set_done(&mut self, id: u64, done: bool) {
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            let (from, to) = if done { (&mut trie.ids, &mut trie.done_ids) } else { (&mut trie.done_ids, &mut trie.ids) };
+            if from.remove(&id) {
+                to.insert(id);
+                for new_trie in trie.children.values_mut() {
+                    tries_to_visit.push(new_trie)
+                }
+            }
+        }
+    }
     fn delete(&mut self, id: u64) {
         let mut tries_to_visit = vec![self];
         while let Some(trie) = tries_to_visit.pop() {
-            if trie.ids.remove(&id) {
+            if trie.ids.remove(&id) | trie.done_ids.remove(&id) {
+                trie.ends.remove(&id);
                 for new_trie in trie.children.values_mut() {
                     tries_to_visit.push(new_trie)
                 }
             }
         }
     }
+    fn children(&self) -> Vec<(char, &Self)> {
+        let mut children: Vec<(char, &Self)> = self.children.iter().map(|(c, trie)| (*c, trie)).collect();
+        children.sort_by_key(|(c, _)| *c);
+        children
+    }
+    fn ends(&self) -> &FxHashMap<u64, usize> {
+        &self.ends
+    }
+    fn clear(&mut self) {
+        self.children.clear();
+        self.ids.clear();
+        self.done_ids.clear();
+        self.ends.clear();
+    }
 }
 impl Default for Trie2 {
     fn default() -> Self {
@@ -288,38 +697,60 @@ impl Default for Trie2 {
 pub struct Trie1 {
     children: FxHashMap<char, Trie1>,
     ids: FxHashSet<u64>,
+    done_ids: FxHashSet<u64>, //ids marked done, which aren't in ids
+    ends: FxHashMap<u64, usize>,
 }
 impl Trie1 {
     fn new() -> Self {
         Trie1{
             children: FxHashMap::default(),
             ids: FxHashSet::default(),
+            done_ids: FxHashSet::default(),
+            ends: FxHashMap::default(),
         }
     }
-    fn add_rec(trie: &mut Trie1, id: u64, insert: &str) {
-        trie.ids.insert(id);
+    fn add_rec(trie: &mut Trie1, ids: &[u64], insert: &str) {
+        trie.ids.extend(ids);
         if let Some(first_char) = insert.chars().nth(0) {
             let trie = trie.children.entry(first_char).or_insert(Trie1::new());
-            Self::add_rec(trie, id, &insert[1..]);
+            Self::add_rec(trie, ids, &insert[1..]);
+        }
+        else {
+            for id in ids {
+                *trie.ends.entry(*id).or_insert(0) += 1;
+            }
         }
     }
-    fn search_rec(trie: &Trie1, search: &str) -> FxHashSet<u64> {
+    fn search_rec(trie: &Trie1, search: &str, include_done: bool) -> FxHashSet<u64> {
         if let Some(first_char) = search.chars().nth(0) {
             let mut results = FxHashSet::default();
             for c in CHARS.iter() {
                 if let Some(trie) = trie.children.get(c) {
                     let new_search = if *c == first_char { &search[1..] } else { search };
-                    results = results.union(&Self::search_rec(trie, new_search)).cloned().collect();
+                    results = results.union(&Self::search_rec(trie, new_search, include_done)).cloned().collect();
                 }
             }
             results
         }
-        else { //search string is empty, we successfully matched whole string, so return ids for current node
+        else if include_done { //search string is empty, we successfully matched whole string, so return ids for current node
+            trie.ids.union(&trie.done_ids).cloned().collect()
+        }
+        else {
             trie.ids.clone()
         }
     }
+    fn set_done_rec(trie: &mut Trie1, id: u64, done: bool) {
+        let (from, to) = if done { (&mut trie.ids, &mut trie.done_ids) } else { (&mut trie.done_ids, &mut trie.ids) };
+        if from.remove(&id) {
+            to.insert(id);
+            for trie in trie.children.values_mut() {
+                Self::set_done_rec(trie, id, done)
+            }
+        }
+    }
     fn delete_rec(trie: &mut Trie1, id: u64) {
-        if trie.ids.remove(&id) {
+        if trie.ids.remove(&id) | trie.done_ids.remove(&id) {
+            trie.ends.remove(&id);
             for trie in trie.children.values_mut() {
                 Self::delete_rec(trie, id)
             }
@@ -329,19 +760,39 @@ impl Trie1 {
 impl Trie for Trie1 {
     fn add(&mut self, id: u64, inserts: Vec<&str>) {
         for insert in inserts {
-            Self::add_rec(self, id, insert)
+            Self::add_rec(self, &[id], insert)
         }
     }
-    fn search(&self, searches: Vec<&str>, _filter: Option<&FxHashSet<u64>>) -> FxHashSet<u64> {
-        let mut matches = searches.iter().map(|search| Self::search_rec(self, search));
+    fn add_many(&mut self, ids: &[u64], insert: &str) {
+        Self::add_rec(self, ids, insert)
+    }
+    fn search_with_done(&self, searches: Vec<&str>, _filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
+        let mut matches = searches.iter().map(|search| Self::search_rec(self, search, include_done));
         if let Some(first_match) = matches.next() {
             return matches.fold(first_match, |acc, next_match| acc.intersection(&next_match).cloned().collect())
         }
         FxHashSet::default()
     }
+    fn set_done(&mut self, id: u64, done: bool) {
+        Self::set_done_rec(self, id, done)
+    }
     fn delete(&mut self, id: u64) {
         Self::delete_rec(self, id)
     }
+    fn children(&self) -> Vec<(char, &Self)> {
+        let mut children: Vec<(char, &Self)> = self.children.iter().map(|(c, trie)| (*c, trie)).collect();
+        children.sort_by_key(|(c, _)| *c);
+        children
+    }
+    fn ends(&self) -> &FxHashMap<u64, usize> {
+        &self.ends
+    }
+    fn clear(&mut self) {
+        self.children.clear();
+        self.ids.clear();
+        self.done_ids.clear();
+        self.ends.clear();
+    }
 }
 impl Default for Trie1 {
     fn default() -> Self {
@@ -349,6 +800,323 @@ impl Default for Trie1 {
     }
 }
 
+//non-recursive, search-match pruning, like Trie3 but for any unicode chars rather than just those in CHARS
+//(children are visited in whatever order they're stored and searches are split on char boundaries rather than bytes)
+//the parser only accepts ASCII words and tags, so other chars can only be stored through this API (see the tests)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharTrie {
+    children: FxHashMap<char, CharTrie>,
+    ids: FxHashSet<u64>,
+    done_ids: FxHashSet<u64>, //ids marked done, which aren't in ids
+    ends: FxHashMap<u64, usize>,
+}
+impl CharTrie {
+    fn new() -> Self {
+        CharTrie{
+            children: FxHashMap::default(),
+            ids: FxHashSet::default(),
+            done_ids: FxHashSet::default(),
+            ends: FxHashMap::default(),
+        }
+    }
+    fn add_single(&mut self, ids: &[u64], insert: &str) {
+        let mut trie = self;
+        trie.ids.extend(ids);
+        for c in insert.chars() {
+            trie = trie.children.entry(c).or_default();
+            trie.ids.extend(ids);
+        }
+        for id in ids {
+            *trie.ends.entry(*id).or_insert(0) += 1;
+        }
+    }
+    fn search_single(&self, search: &str, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
+        let mut results = FxHashSet::default();
+        let mut tries_to_visit = vec![(self, search)];
+        'trie: while let Some((trie, search)) = tries_to_visit.pop() {
+            if let Some(f) = filter { 
+                if !f.iter().any(|id| trie.ids.contains(id) || (include_done && trie.done_ids.contains(id))) { //skip branches which don't contain an index in the filter
+                    continue 'trie
+                }
+            }
+            if let Some(first_char) = search.chars().next() {
+                for (c, new_trie) in trie.children.iter() {
+                    let new_search = if *c == first_char { &search[first_char.len_utf8()..] } else { search };
+                    tries_to_visit.push((new_trie, new_search));
+                }
+            }
+            else {
+                results.extend(trie.ids.iter().cloned());
+                if include_done {
+                    results.extend(trie.done_ids.iter().cloned());
+                }
+            }
+        }
+        results
+    }
+}
+impl Trie for CharTrie {
+    fn add(&mut self, id: u64, inserts: Vec<&str>) {
+        for insert in inserts {
+            Self::add_single(self, &[id], insert)
+        }
+    }
+    fn add_many(&mut self, ids: &[u64], insert: &str) {
+        Self::add_single(self, ids, insert)
+    }
+    fn search_with_done(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
+        let mut searches = searches.iter();
+        if let Some(first_search) = searches.next() {
+            let mut result = Self::search_single(self, first_search, filter, include_done);
+            for search in searches { //use results of previous searches to filter ids in subsequent searches
+                result = result.intersection(&Self::search_single(self, search, Some(&result), include_done)).cloned().collect();
+            }
+            result
+        }
+        else {
+            FxHashSet::default()
+        }
+    }
+    fn set_done(&mut self, id: u64, done: bool) {
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            let (from, to) = if done { (&mut trie.ids, &mut trie.done_ids) } else { (&mut trie.done_ids, &mut trie.ids) };
+            if from.remove(&id) {
+                to.insert(id);
+                for new_trie in trie.children.values_mut() {
+                    tries_to_visit.push(new_trie)
+                }
+            }
+        }
+    }
+    fn delete(&mut self, id: u64) {
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            if trie.ids.remove(&id) | trie.done_ids.remove(&id) {
+                trie.ends.remove(&id);
+                for new_trie in trie.children.values_mut() {
+                    tries_to_visit.push(new_trie)
+                }
+            }
+        }
+    }
+    fn children(&self) -> Vec<(char, &Self)> {
+        let mut children: Vec<(char, &Self)> = self.children.iter().map(|(c, trie)| (*c, trie)).collect();
+        children.sort_by_key(|(c, _)| *c);
+        children
+    }
+    fn ends(&self) -> &FxHashMap<u64, usize> {
+        &self.ends
+    }
+    fn clear(&mut self) {
+        self.children.clear();
+        self.ids.clear();
+        self.done_ids.clear();
+        self.ends.clear();
+    }
+}
+impl Default for CharTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//path compressed (a radix tree), a run of chars which doesn't branch (and where no word ends) is a single node
+//otherwise like CharTrie, so searches use search-match pruning and any unicode chars can be stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadixTrie {
+    label: String, //the chars on the edge from the parent to this node, empty for the root
+    children: FxHashMap<char, RadixTrie>, //keyed by the first char of their label
+    ids: FxHashSet<u64>,
+    done_ids: FxHashSet<u64>, //ids marked done, which aren't in ids
+    ends: FxHashMap<u64, usize>,
+}
+impl RadixTrie {
+    fn new() -> Self {
+        RadixTrie{
+            label: String::new(),
+            children: FxHashMap::default(),
+            ids: FxHashSet::default(),
+            done_ids: FxHashSet::default(),
+            ends: FxHashMap::default(),
+        }
+    }
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+    //shortens the label to its first at bytes, moving everything else into a new child holding the rest of it
+    fn split(&mut self, at: usize) {
+        let tail = RadixTrie {
+            label: self.label.split_off(at),
+            children: mem::take(&mut self.children),
+            ids: self.ids.clone(),
+            done_ids: self.done_ids.clone(),
+            ends: mem::take(&mut self.ends),
+        };
+        self.children.insert(tail.label.chars().next().unwrap(), tail); //at is always before the end of the label
+    }
+    fn add_single(&mut self, ids: &[u64], insert: &str) {
+        let mut trie = self;
+        trie.ids.extend(ids);
+        let mut rest = insert;
+        while let Some(first_char) = rest.chars().next() {
+            let child = trie.children.entry(first_char).or_insert_with(|| RadixTrie { label: rest.to_owned(), ..RadixTrie::new() });
+            let common = common_prefix_len(&child.label, rest);
+            if common < child.label.len() {
+                child.split(common);
+            }
+            child.ids.extend(ids);
+            rest = &rest[common..];
+            trie = child;
+        }
+        for id in ids {
+            *trie.ends.entry(*id).or_insert(0) += 1;
+        }
+    }
+    fn search_single(&self, search: &str, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
+        let mut results = FxHashSet::default();
+        let mut tries_to_visit = vec![(self, search)];
+        'trie: while let Some((trie, search)) = tries_to_visit.pop() {
+            if let Some(f) = filter { 
+                if !f.iter().any(|id| trie.ids.contains(id) || (include_done && trie.done_ids.contains(id))) { //skip branches which don't contain an index in the filter
+                    continue 'trie
+                }
+            }
+            if search.is_empty() {
+                results.extend(trie.ids.iter().cloned());
+                if include_done {
+                    results.extend(trie.done_ids.iter().cloned());
+                }
+                continue 'trie
+            }
+            for new_trie in trie.children.values() {
+                //matching as much of the search as possible along the label is never worse, since gaps are allowed
+                let mut new_search = search;
+                for c in new_trie.label.chars() {
+                    match new_search.chars().next() {
+                        Some(first_char) if first_char == c => new_search = &new_search[c.len_utf8()..],
+                        Some(_) => (),
+                        None => break,
+                    }
+                }
+                tries_to_visit.push((new_trie, new_search));
+            }
+        }
+        results
+    }
+}
+impl Trie for RadixTrie {
+    fn add(&mut self, id: u64, inserts: Vec<&str>) {
+        for insert in inserts {
+            Self::add_single(self, &[id], insert)
+        }
+    }
+    fn add_many(&mut self, ids: &[u64], insert: &str) {
+        Self::add_single(self, ids, insert)
+    }
+    fn search_with_done(&self, searches: Vec<&str>, filter: Option<&FxHashSet<u64>>, include_done: bool) -> FxHashSet<u64> {
+        let mut searches = searches.iter();
+        if let Some(first_search) = searches.next() {
+            let mut result = Self::search_single(self, first_search, filter, include_done);
+            for search in searches { //use results of previous searches to filter ids in subsequent searches
+                result = result.intersection(&Self::search_single(self, search, Some(&result), include_done)).cloned().collect();
+            }
+            result
+        }
+        else {
+            FxHashSet::default()
+        }
+    }
+    fn set_done(&mut self, id: u64, done: bool) {
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            let (from, to) = if done { (&mut trie.ids, &mut trie.done_ids) } else { (&mut trie.done_ids, &mut trie.ids) };
+            if from.remove(&id) {
+                to.insert(id);
+                for new_trie in trie.children.values_mut() {
+                    tries_to_visit.push(new_trie)
+                }
+            }
+        }
+    }
+    fn delete(&mut self, id: u64) {
+        let mut tries_to_visit = vec![self];
+        while let Some(trie) = tries_to_visit.pop() {
+            if trie.ids.remove(&id) | trie.done_ids.remove(&id) {
+                trie.ends.remove(&id);
+                for new_trie in trie.children.values_mut() {
+                    tries_to_visit.push(new_trie)
+                }
+            }
+        }
+    }
+    //children are ordered by the first char of their label
+    fn children(&self) -> Vec<(char, &Self)> {
+        let mut children: Vec<(char, &Self)> = self.children.iter().map(|(c, trie)| (*c, trie)).collect();
+        children.sort_by_key(|(c, _)| *c);
+        children
+    }
+    fn ends(&self) -> &FxHashMap<u64, usize> {
+        &self.ends
+    }
+    fn clear(&mut self) {
+        self.children.clear();
+        self.ids.clear();
+        self.done_ids.clear();
+        self.ends.clear();
+    }
+    fn edge_text(&self, _: char) -> String {
+        self.label.clone()
+    }
+    //the same search as the default, except that the path is extended by a whole label at a time
+    fn complete_word_fuzzy(&self, prefix: &str, max_dist: usize, limit: usize) -> Vec<String> {
+        let prefix: Vec<char> = prefix.chars().collect();
+        let mut completions = Vec::new();
+        let initial_match = if prefix.len() <= max_dist { Some(prefix.len()) } else { None };
+        let mut tries_to_visit = vec![(self, String::new(), (0..=prefix.len()).collect::<Vec<usize>>(), initial_match)];
+        'trie: while let Some((trie, mut path, mut row, mut matched)) = tries_to_visit.pop() {
+            for c in trie.label.chars() {
+                if matched.is_none() && row.iter().min().unwrap() > &max_dist {
+                    continue 'trie
+                }
+                row = next_edit_row(&prefix, &row, c);
+                path.push(c);
+                matched = match (matched, row[prefix.len()]) {
+                    (Some(best), dist) => Some(dist.min(best)),
+                    (None, dist) if dist <= max_dist => Some(dist),
+                    (None, _) => None,
+                };
+            }
+            if let Some(dist) = matched {
+                if !trie.ends.is_empty() {
+                    completions.push((dist, path.clone()));
+                }
+            }
+            else if row.iter().min().unwrap() > &max_dist {
+                continue 'trie
+            }
+            for (_, child) in trie.children() {
+                tries_to_visit.push((child, path.clone(), row.clone(), matched));
+            }
+        }
+        completions.sort();
+        completions.into_iter().take(limit).map(|(_, word)| word).collect()
+    }
+}
+impl Default for RadixTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+//length in bytes of the longest common prefix of a and b
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .take_while(|((_, x), y)| x == y)
+        .last()
+        .map_or(0, |((i, x), _)| i + x.len_utf8())
+}
+
 //non-recursive, search-match pruning and depth pruning
 // #[derive(Debug, Clone, PartialEq, Eq)]
 // pub struct Trie5 {
@@ -424,4 +1192,296 @@ impl Default for Trie1 {
 //     fn default() -> Self {
 //         Self::new(32, 32)
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //runs a generic check against every trie implementation
+    macro_rules! for_each_trie {
+        ($check:ident) => {
+            $check(Trie1::default());
+            $check(Trie2::default());
+            $check(Trie3::default());
+            $check(Trie4::default());
+            $check(CharTrie::default());
+            $check(RadixTrie::default());
+        };
+    }
+
+    fn sample<T: Trie>(mut trie: T) -> T {
+        trie.add(0, vec!["water", "plants"]);
+        trie.add(1, vec!["wash", "car"]);
+        trie.add(2, vec!["write", "report", "tomorrow"]);
+        trie.add(3, vec!["walk", "dog", "twice"]);
+        trie
+    }
+
+    #[test]
+    fn search_sorted_matches_search() {
+        fn check<T: Trie>(trie: T) {
+            let trie = sample(trie);
+            for key in ["w", "wa", "ar", "t", "rrw", "z", ""] {
+                let mut expected: Vec<u64> = trie.search(vec![key], None).into_iter().collect();
+                expected.sort_unstable();
+                assert_eq!(trie.search_sorted(key), expected, "searching for {:?}", key);
+            }
+            assert_eq!(trie.search_sorted("wa"), vec![0, 1, 3]);
+        }
+        for_each_trie!(check);
+    }
+
+    //the parser only gives ASCII words, so non-ASCII text only reaches these tries through the library
+    #[test]
+    fn unicode_words() {
+        fn check<T: Trie>(mut trie: T) {
+            trie.add(0, vec!["日本語", "café"]);
+            trie.add(1, vec!["日本", "cafe"]);
+            trie.add(2, vec!["語学"]);
+            assert_eq!(trie.search_sorted("日本"), vec![0, 1]);
+            assert_eq!(trie.search_sorted("語"), vec![0, 2]);
+            assert_eq!(trie.search_sorted("cé"), vec![0]);
+            assert_eq!(trie.search_sorted("é"), vec![0]);
+            assert_eq!(trie.search_sorted("本語"), vec![0]);
+            let exact = |word| trie.ends_at(word).map(|ends| ends.keys().copied().collect::<Vec<u64>>());
+            assert_eq!(exact("日本"), Some(vec![1]));
+            assert_eq!(exact("日"), None); //only a prefix of stored words
+            assert_eq!(exact("café"), Some(vec![0]));
+            assert_eq!(trie.longest_prefix("日本語学"), (String::from("日本語"), vec![0]));
+            assert_eq!(trie.longest_prefix("cafés"), (String::from("café"), vec![0]));
+            assert_eq!(trie.longest_prefix("caf"), (String::new(), Vec::new()));
+        }
+        check(CharTrie::default());
+        check(RadixTrie::default());
+    }
+
+
+    #[test]
+    fn clear_empties_trie() {
+        fn check<T: Trie + Default>(trie: T) {
+            let baseline = T::default().node_count();
+            let mut trie = sample(trie);
+            assert!(trie.node_count() > baseline);
+            trie.clear();
+            assert_eq!(trie.node_count(), baseline);
+            assert_eq!(trie.word_count(), 0);
+            assert!(trie.search_sorted("wa").is_empty());
+            assert!(trie.search(vec![""], None).is_empty());
+            trie.add(4, vec!["wash"]); //still usable
+            assert_eq!(trie.search_sorted("wa"), vec![4]);
+        }
+        for_each_trie!(check);
+    }
+
+
+    #[test]
+    fn radix_trie_compresses_long_words() {
+        let mut trie1 = Trie1::default();
+        let mut radix = RadixTrie::default();
+        let long_words = ["internationalization", "interoperability", "intercontinental", "incomprehensibilities"];
+        for (id, word) in long_words.iter().enumerate() {
+            trie1.add(id as u64, vec![word]);
+            radix.add(id as u64, vec![word]);
+        }
+        for key in ["inter", "ion", "tl", "incomp", "zz", "i"] {
+            assert_eq!(radix.search_sorted(key), trie1.search_sorted(key), "searching for {:?}", key);
+        }
+        //a node per branch rather than per character
+        assert!(radix.node_count() * 5 < trie1.node_count(), "{} radix nodes against {}", radix.node_count(), trie1.node_count());
+    }
+
+
+    #[test]
+    fn dot_output() {
+        fn check<T: Trie>(mut trie: T) {
+            trie.add(0, vec!["ab"]);
+            trie.add(1, vec!["ac", "a"]);
+            trie.add(2, vec!["a"]);
+            assert_eq!(trie.to_dot(), [
+                "digraph trie {",
+                "    n0 [label=\"\"];",
+                "    n1 [label=\"a (2)\", peripheries=2];",
+                "    n0 -> n1;",
+                "    n2 [label=\"b (1)\", peripheries=2];",
+                "    n1 -> n2;",
+                "    n3 [label=\"c (1)\", peripheries=2];",
+                "    n1 -> n3;",
+                "}",
+            ].join("\n"));
+        }
+        for_each_trie!(check);
+    }
+
+
+    #[test]
+    fn done_ids_are_skipped() {
+        fn check<T: Trie>(trie: T) {
+            let mut trie = sample(trie);
+            trie.set_done(1, true);
+            trie.set_done(3, true);
+            assert_eq!(trie.search_sorted("wa"), vec![0]);
+            let mut with_done: Vec<u64> = trie.search_with_done(vec!["wa"], None, true).into_iter().collect();
+            with_done.sort_unstable();
+            assert_eq!(with_done, vec![0, 1, 3]);
+            let both = |include_done| {
+                let mut ids: Vec<u64> = trie.search_with_done(vec!["wa", "r"], None, include_done).into_iter().collect();
+                ids.sort_unstable();
+                ids
+            };
+            assert_eq!((both(false), both(true)), (vec![0], vec![0, 1]));
+            let filter: FxHashSet<u64> = [1].iter().copied().collect(); //a done id in the filter doesn't prune its branch when it's included
+            assert!(trie.search_with_done(vec!["car"], Some(&filter), true).contains(&1));
+            #[cfg(debug_assertions)]
+            assert_eq!(trie.check_invariants(), Ok(()));
+            trie.set_done(1, false);
+            assert_eq!(trie.search_sorted("wa"), vec![0, 1]);
+            trie.delete(3);
+            assert!(!trie.search_with_done(vec!["wa"], None, true).contains(&3));
+            assert_eq!(trie.word_count(), 7); //water plants wash car write report tomorrow
+        }
+        for_each_trie!(check);
+    }
+
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn invariants_after_operations() {
+        fn check<T: Trie>(trie: T) {
+            let mut trie = sample(trie);
+            assert_eq!(trie.check_invariants(), Ok(()));
+            trie.delete(1);
+            trie.add_many(&[4, 5], "wander");
+            trie.set_done(4, true);
+            assert_eq!(trie.check_invariants(), Ok(()));
+            trie.clear();
+            assert_eq!(trie.check_invariants(), Ok(()));
+        }
+        for_each_trie!(check);
+        //corrupted by hand
+        let mut trie = sample(Trie1::default());
+        trie.children.get_mut(&'w').unwrap().ends.insert(0, 0);
+        assert_eq!(trie.check_invariants(), Err(String::from("id 0 ends at \"w\" zero times")));
+        let mut trie = sample(Trie1::default());
+        trie.children.get_mut(&'w').unwrap().ends.insert(9, 1); //without 9 in the ids a search finds
+        assert_eq!(trie.check_invariants(), Err(String::from("id 9 ends at \"w\" but isn't found by searching for it")));
+        let mut trie = sample(RadixTrie::default());
+        trie.children.get_mut(&'w').unwrap().ends.insert(9, 1);
+        assert!(trie.check_invariants().is_err());
+    }
+
+    #[test]
+    fn longest_stored_prefix() {
+        fn check<T: Trie>(mut trie: T) {
+            trie.add(4, vec!["cat", "food"]);
+            trie.add(2, vec!["cat"]);
+            trie.add(7, vec!["catalog", "ca"]);
+            assert_eq!(trie.longest_prefix("catalogue"), (String::from("catalog"), vec![7]));
+            assert_eq!(trie.longest_prefix("catapult"), (String::from("cat"), vec![2, 4]));
+            assert_eq!(trie.longest_prefix("cab"), (String::from("ca"), vec![7]));
+            assert_eq!(trie.longest_prefix("dog"), (String::new(), Vec::new()));
+            trie.delete(7);
+            assert_eq!(trie.longest_prefix("catalogue"), (String::from("cat"), vec![2, 4]));
+            assert_eq!(trie.longest_prefix("cab"), (String::new(), Vec::new()));
+        }
+        for_each_trie!(check);
+    }
+
+    #[test]
+    fn terminals_in_order() {
+        fn check<T: Trie>(mut trie: T) {
+            trie.add(0, vec!["water", "plants"]);
+            trie.add(1, vec!["wash", "car", "car"]);
+            trie.add(2, vec!["was", "water"]);
+            trie.add(3, vec!["plan"]);
+            trie.delete(3);
+            let terminals: Vec<(String, Vec<(u64, usize)>)> = trie.iter_terminals().map(|(word, ends)| {
+                let mut ends: Vec<(u64, usize)> = ends.iter().map(|(id, count)| (*id, *count)).collect();
+                ends.sort_unstable();
+                (word, ends)
+            }).collect();
+            assert_eq!(terminals, vec![
+                (String::from("car"), vec![(1, 2)]),
+                (String::from("plants"), vec![(0, 1)]),
+                (String::from("was"), vec![(2, 1)]),
+                (String::from("wash"), vec![(1, 1)]),
+                (String::from("water"), vec![(0, 1), (2, 1)]),
+            ]);
+        }
+        for_each_trie!(check);
+    }
+
+    #[test]
+    fn counts_of_matching_words() {
+        fn check<T: Trie>(trie: T) {
+            let mut trie = sample(trie);
+            trie.add(4, vec!["walk", "the", "walkers", "walk"]);
+            let counts = trie.search_with_counts("wlk");
+            assert_eq!(counts.get(&4), Some(&3));
+            assert_eq!(counts.get(&3), Some(&1));
+            assert_eq!(counts.len(), 2);
+            //the same as checking every stored word
+            for key in ["w", "wa", "ar", "t", "rrw", "z", "", "walkers"] {
+                let mut expected: FxHashMap<u64, usize> = FxHashMap::default();
+                for (word, ends) in trie.iter_terminals() {
+                    if crate::matching::match_word(&word, key, crate::MatchMode::Subsequence, false) {
+                        for (id, count) in ends {
+                            *expected.entry(*id).or_insert(0) += count;
+                        }
+                    }
+                }
+                assert_eq!(trie.search_with_counts(key), expected, "counting {:?}", key);
+            }
+        }
+        for_each_trie!(check);
+    }
+
+    #[test]
+    fn fuzzy_completions() {
+        fn check<T: Trie>(mut trie: T) {
+            trie.add(0, vec!["deploy", "report"]);
+            trie.add(1, vec!["deployment", "debloat"]);
+            trie.add(2, vec!["depot", "delay", "dog"]);
+            trie.add(3, vec!["depots"]);
+            trie.delete(3);
+            //one typo in the prefix
+            assert_eq!(trie.complete_word_fuzzy("depoy", 1, 10), vec!["deploy", "deployment", "depot"]);
+            assert_eq!(trie.complete_word_fuzzy("depoy", 0, 10), Vec::<String>::new());
+            //the closest first, then alphabetically
+            assert_eq!(trie.complete_word_fuzzy("deplo", 1, 10), vec!["deploy", "deployment", "debloat", "depot"]);
+            assert_eq!(trie.complete_word_fuzzy("deplo", 2, 10), vec!["deploy", "deployment", "debloat", "depot", "delay", "report"]); //"repo" is two edits away
+            assert_eq!(trie.complete_word_fuzzy("deplo", 0, 10), vec!["deploy", "deployment"]);
+            assert_eq!(trie.complete_word_fuzzy("deplo", 2, 3), vec!["deploy", "deployment", "debloat"]);
+            assert_eq!(trie.complete_word_fuzzy("deplo", 2, 0), Vec::<String>::new());
+            assert_eq!(trie.complete_word_fuzzy("", 0, 2), vec!["debloat", "delay"]);
+            //ending, or with the typo, partway along a path compressed edge
+            assert_eq!(trie.complete_word_fuzzy("deploym", 0, 10), vec!["deployment"]);
+            assert_eq!(trie.complete_word_fuzzy("deploymnt", 1, 10), vec!["deployment"]);
+            assert_eq!(trie.complete_word_fuzzy("repirt", 1, 10), vec!["report"]);
+        }
+        for_each_trie!(check);
+    }
+
+    #[test]
+    fn add_many_like_adding_each() {
+        fn check<T: Trie + Clone>(trie: T) {
+            let mut bulk = sample(trie);
+            let mut each = bulk.clone();
+            let ids = [7, 9, 12, 30];
+            bulk.add_many(&ids, "deploy");
+            for id in ids.iter() {
+                each.add(*id, vec!["deploy"]);
+            }
+            let mut found: Vec<u64> = bulk.search(vec!["deploy"], None).into_iter().collect();
+            found.sort_unstable();
+            assert_eq!(found, ids.to_vec());
+            assert_eq!(bulk.search_sorted("dpl"), ids.to_vec());
+            assert_eq!(bulk.search_sorted("wa"), vec![0, 1, 3]); //the other words are untouched
+            assert_eq!(bulk.node_count(), each.node_count());
+            assert_eq!(bulk.ends_at("deploy"), each.ends_at("deploy"));
+            #[cfg(debug_assertions)]
+            assert_eq!(bulk.check_invariants(), Ok(()));
+        }
+        for_each_trie!(check);
+    }
+}